@@ -19,8 +19,8 @@ pub fn synth(
     let freq1 = PitchToFreq::default().node(graph, &pitch + &detune);
     let freq2 = PitchToFreq::default().node(graph, &pitch - &detune);
 
-    let saw1 = BlSawOscillator::default().node(graph, freq1);
-    let saw2 = BlSawOscillator::default().node(graph, freq2);
+    let saw1 = BlSawOscillator::default().node(graph, freq1, (), (), ());
+    let saw2 = BlSawOscillator::default().node(graph, freq2, (), (), ());
 
     // let saws = (saw1 + saw2) * 0.5;
     let saws = saw1;
@@ -28,7 +28,7 @@ pub fn synth(
     let adsr = Adsr::default().node(graph, gate, attack, decay, sustain, release);
     let adsr = adsr.output(0).scale(20.0, 20_000.0);
 
-    Biquad::lowpass().node(graph, saws, adsr, 20.0, 0.01)
+    Biquad::lowpass().node(graph, saws, adsr, 20.0, 0.01, ())
 }
 
 fn main() {
@@ -0,0 +1,177 @@
+use std::f64::consts::PI;
+
+pub use rustfft::num_complex::Complex64;
+
+use super::{Biquad, BiquadState, CascadeFilter, Svf, design_into};
+
+/// Frequency-domain analysis of a filter's current coefficients, e.g. for drawing response curves.
+pub trait FrequencyResponse {
+    /// The complex response at `frequency` Hz.
+    fn response(&self, frequency: f32, sample_rate: f32) -> Complex64;
+
+    /// The filter's poles in the z-plane.
+    fn poles(&self) -> Vec<Complex64>;
+
+    /// The filter's finite zeros in the z-plane.
+    fn zeros(&self) -> Vec<Complex64>;
+
+    /// The gain at `frequency` Hz, in decibels.
+    fn magnitude_db(&self, frequency: f32, sample_rate: f32) -> f32 {
+        let magnitude = self.response(frequency, sample_rate).norm();
+        (20.0 * magnitude.max(1e-12).log10()) as f32
+    }
+
+    /// The phase shift at `frequency` Hz, in radians between -pi and pi.
+    fn phase(&self, frequency: f32, sample_rate: f32) -> f32 {
+        self.response(frequency, sample_rate).arg() as f32
+    }
+
+    /// The group delay at `frequency` Hz, in seconds.
+    fn group_delay(&self, frequency: f32, sample_rate: f32) -> f32 {
+        let df = 1e-5 * sample_rate;
+        // comparing the phase on either side avoids having to unwrap it
+        let ratio = self.response(frequency + df, sample_rate)
+            * self.response(frequency - df, sample_rate).conj();
+        (-ratio.arg() / (4.0 * PI * df as f64)) as f32
+    }
+}
+
+/// Finds the roots of `c0 * z^2 + c1 * z + c2`, dropping any that lie at infinity.
+fn quadratic_roots(c0: f32, c1: f32, c2: f32) -> Vec<Complex64> {
+    let (c0, c1, c2) = (c0 as f64, c1 as f64, c2 as f64);
+    if c0.abs() > f64::EPSILON {
+        let d = Complex64::new(c1 * c1 - 4.0 * c0 * c2, 0.0).sqrt();
+        vec![(-c1 + d) / (2.0 * c0), (-c1 - d) / (2.0 * c0)]
+    } else if c1.abs() > f64::EPSILON {
+        vec![Complex64::new(-c2 / c1, 0.0)]
+    } else {
+        Vec::new()
+    }
+}
+
+impl FrequencyResponse for BiquadState {
+    fn response(&self, frequency: f32, sample_rate: f32) -> Complex64 {
+        let omega = 2.0 * PI * frequency as f64 / sample_rate as f64;
+        let zi = Complex64::from_polar(1.0, -omega);
+        let num = self.b0 as f64 + zi * (self.b1 as f64 + zi * self.b2 as f64);
+        let den = 1.0 + zi * (self.a1 as f64 + zi * self.a2 as f64);
+        num / den
+    }
+
+    fn poles(&self) -> Vec<Complex64> {
+        quadratic_roots(1.0, self.a1, self.a2)
+    }
+
+    fn zeros(&self) -> Vec<Complex64> {
+        quadratic_roots(self.b0, self.b1, self.b2)
+    }
+}
+
+/// A cascade of sections, each feeding the next.
+impl FrequencyResponse for [BiquadState] {
+    fn response(&self, frequency: f32, sample_rate: f32) -> Complex64 {
+        self.iter()
+            .map(|section| section.response(frequency, sample_rate))
+            .product()
+    }
+
+    fn poles(&self) -> Vec<Complex64> {
+        self.iter().flat_map(|section| section.poles()).collect()
+    }
+
+    fn zeros(&self) -> Vec<Complex64> {
+        self.iter().flat_map(|section| section.zeros()).collect()
+    }
+}
+
+/// `response` designs the filter from its current inputs at the requested sample rate, so it is meaningful
+/// before the filter has processed anything. `poles` and `zeros` report the coefficients the processor last
+/// designed, and are only meaningful once it has run.
+impl FrequencyResponse for Biquad {
+    fn response(&self, frequency: f32, sample_rate: f32) -> Complex64 {
+        let mut section = BiquadState {
+            mode: self.mode,
+            ..Default::default()
+        };
+        // invalid coefficients mute the section, which is an accurate response
+        let _ = section.update(self.cutoff, self.q, self.amp, sample_rate);
+        section.response(frequency, sample_rate)
+    }
+
+    fn poles(&self) -> Vec<Complex64> {
+        self.state.poles()
+    }
+
+    fn zeros(&self) -> Vec<Complex64> {
+        self.state.zeros()
+    }
+}
+
+/// `response` designs the cascade from its current inputs at the requested sample rate, so it is meaningful
+/// before the filter has processed anything. `poles` and `zeros` report the coefficients the processor last
+/// designed, and are only meaningful once it has run.
+impl FrequencyResponse for CascadeFilter {
+    fn response(&self, frequency: f32, sample_rate: f32) -> Complex64 {
+        let mut sections = vec![BiquadState::default(); self.sections.len()];
+        design_into(
+            &self.prototype,
+            self.design,
+            self.mode,
+            self.cutoff,
+            self.q,
+            sample_rate,
+            &mut sections,
+        );
+        sections.response(frequency, sample_rate)
+    }
+
+    fn poles(&self) -> Vec<Complex64> {
+        self.sections.poles()
+    }
+
+    fn zeros(&self) -> Vec<Complex64> {
+        self.sections.zeros()
+    }
+}
+
+/// One of the outputs of an [`Svf`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SvfOutput {
+    Lowpass,
+    Highpass,
+    Bandpass,
+    Notch,
+    Peak,
+}
+
+impl Svf {
+    /// The biquad with the same response as one output of this filter at its current `cutoff` and `q`.
+    ///
+    /// The filter has several outputs, so it doesn't implement [`FrequencyResponse`] itself; analyze the
+    /// returned section instead.
+    pub fn equivalent_biquad(&self, output: SvfOutput, sample_rate: f32) -> BiquadState {
+        // the bilinear transform of 1 / (s^2 + k s + 1), prewarped the same way as the filter
+        let cutoff = self.cutoff.clamp(0.0, 0.49 * sample_rate);
+        let g = (std::f32::consts::PI * cutoff / sample_rate).tan();
+        let k = 1.0 / self.q.max(0.01);
+        let g2 = g * g;
+
+        let a0 = 1.0 + g * (g + k);
+        let [b0, b1, b2] = match output {
+            SvfOutput::Lowpass => [g2, 2.0 * g2, g2],
+            SvfOutput::Highpass => [1.0, -2.0, 1.0],
+            SvfOutput::Bandpass => [g, 0.0, -g],
+            SvfOutput::Notch => [1.0 + g2, 2.0 * (g2 - 1.0), 1.0 + g2],
+            SvfOutput::Peak => [g2 - 1.0, 2.0 * (g2 + 1.0), g2 - 1.0],
+        };
+
+        BiquadState {
+            b0: b0 / a0,
+            b1: b1 / a0,
+            b2: b2 / a0,
+            a1: 2.0 * (g2 - 1.0) / a0,
+            a2: (1.0 - g * k + g2) / a0,
+            ..Default::default()
+        }
+    }
+}
@@ -0,0 +1,163 @@
+use raug::prelude::*;
+
+/// The largest integration step taken in one go; faster rates are split into several steps.
+const MAX_STEP: f32 = 0.01;
+const MAX_SUBSTEPS: usize = 16;
+
+/// Advances `state` by `dt` using fourth-order Runge-Kutta, substepping when `dt` is large.
+#[inline]
+fn integrate(state: &mut [f32; 3], dt: f32, f: impl Fn([f32; 3]) -> [f32; 3]) {
+    if !dt.is_finite() {
+        return;
+    }
+    let steps = ((dt.abs() / MAX_STEP).ceil() as usize).clamp(1, MAX_SUBSTEPS);
+    let h = dt / steps as f32;
+
+    let add =
+        |a: [f32; 3], b: [f32; 3], s: f32| [a[0] + b[0] * s, a[1] + b[1] * s, a[2] + b[2] * s];
+
+    for _ in 0..steps {
+        let k1 = f(*state);
+        let k2 = f(add(*state, k1, 0.5 * h));
+        let k3 = f(add(*state, k2, 0.5 * h));
+        let k4 = f(add(*state, k3, h));
+        for i in 0..3 {
+            state[i] += h / 6.0 * (k1[i] + 2.0 * k2[i] + 2.0 * k3[i] + k4[i]);
+        }
+    }
+}
+
+/// The Lorenz attractor.
+///
+/// `rate` scales how fast the system evolves (in system time units per second). The outputs are
+/// normalized to roughly -1 to 1 for the classic parameters.
+#[processor]
+pub fn lorenz(
+    env: ProcEnv,
+    #[state] state: &mut [f32; 3],
+    #[input] rate: &f32,
+    #[input] sigma: &f32,
+    #[input] rho: &f32,
+    #[input] beta: &f32,
+    #[output] x: &mut f32,
+    #[output] y: &mut f32,
+    #[output] z: &mut f32,
+) -> ProcResult<()> {
+    integrate(state, rate / env.sample_rate, |[x, y, z]| {
+        [sigma * (y - x), x * (rho - z) - y, x * y - beta * z]
+    });
+
+    if state.iter().any(|v| !v.is_finite()) {
+        *state = [1.0, 1.0, 1.0];
+    }
+
+    *x = (state[0] / 20.0).clamp(-1.0, 1.0);
+    *y = (state[1] / 27.0).clamp(-1.0, 1.0);
+    *z = (state[2] / 25.0 - 1.0).clamp(-1.0, 1.0);
+
+    Ok(())
+}
+
+impl Default for Lorenz {
+    fn default() -> Self {
+        Self {
+            state: [1.0, 1.0, 1.0],
+            rate: 1.0,
+            sigma: 10.0,
+            rho: 28.0,
+            beta: 8.0 / 3.0,
+        }
+    }
+}
+
+/// The Rössler attractor.
+///
+/// `rate` scales how fast the system evolves (in system time units per second). The outputs are
+/// normalized to roughly -1 to 1 for the classic parameters.
+#[processor]
+pub fn rossler(
+    env: ProcEnv,
+    #[state] state: &mut [f32; 3],
+    #[input] rate: &f32,
+    #[input] a: &f32,
+    #[input] b: &f32,
+    #[input] c: &f32,
+    #[output] x: &mut f32,
+    #[output] y: &mut f32,
+    #[output] z: &mut f32,
+) -> ProcResult<()> {
+    integrate(state, rate / env.sample_rate, |[x, y, z]| {
+        [-y - z, x + a * y, b + z * (x - c)]
+    });
+
+    if state.iter().any(|v| !v.is_finite()) {
+        *state = [0.1, 0.0, 0.0];
+    }
+
+    *x = (state[0] / 12.0).clamp(-1.0, 1.0);
+    *y = (state[1] / 12.0).clamp(-1.0, 1.0);
+    *z = (state[2] / 12.5 - 1.0).clamp(-1.0, 1.0);
+
+    Ok(())
+}
+
+impl Default for Rossler {
+    fn default() -> Self {
+        Self {
+            state: [0.1, 0.0, 0.0],
+            rate: 1.0,
+            a: 0.2,
+            b: 0.2,
+            c: 5.7,
+        }
+    }
+}
+
+/// The logistic map, iterated `rate` times per second.
+///
+/// The output glides smoothly from one iteration to the next, scaled to -1 to 1.
+#[processor]
+pub fn logistic_map(
+    env: ProcEnv,
+    #[state] values: &mut [f32; 2],
+    #[state] t: &mut f32,
+    #[input] rate: &f32,
+    #[input] r: &f32,
+    #[output] out: &mut f32,
+) -> ProcResult<()> {
+    let r = r.clamp(0.0, 4.0);
+
+    *t += rate.max(0.0) / env.sample_rate;
+    if !t.is_finite() {
+        *t = 0.0;
+    }
+
+    // at very high rates, skip iterations rather than spend unbounded time per sample
+    let steps = (*t as usize).min(MAX_SUBSTEPS);
+    *t = t.fract();
+    for _ in 0..steps {
+        values[0] = values[1];
+        values[1] = r * values[1] * (1.0 - values[1]);
+        if !(values[1] > 0.0 && values[1] < 1.0) {
+            // stuck on a fixed point (or diverged), so kick it back into motion
+            values[1] = 0.5;
+        }
+    }
+
+    let s = 0.5 - 0.5 * (std::f32::consts::PI * *t).cos();
+    let value = values[0] + (values[1] - values[0]) * s;
+    *out = 2.0 * value - 1.0;
+
+    Ok(())
+}
+
+impl Default for LogisticMap {
+    fn default() -> Self {
+        Self {
+            values: [0.5, 0.5],
+            t: 0.0,
+            rate: 10.0,
+            r: 3.9,
+        }
+    }
+}
@@ -0,0 +1,272 @@
+use std::sync::Arc;
+
+use raug::prelude::*;
+use rustfft::{Fft, FftPlanner, num_complex::Complex};
+use thiserror::Error;
+
+use super::SampleStorage;
+
+#[derive(Error, Debug)]
+pub enum ConvolverError {
+    #[error("Failed to load impulse response: {0}")]
+    Load(#[from] hound::Error),
+    #[error("Unsupported impulse response channel count: {0}")]
+    UnsupportedChannels(usize),
+}
+
+/// Uniformly partitioned overlap-save convolution of one signal with one impulse response.
+///
+/// The impulse response is split into partitions of `block_size` samples, so the output lags the input
+/// by exactly `block_size` samples no matter how long the response is.
+#[derive(Clone)]
+pub struct PartitionedConvolver {
+    block_size: usize,
+    fft: Arc<dyn Fft<f32>>,
+    ifft: Arc<dyn Fft<f32>>,
+    /// The spectrum of each partition of the impulse response.
+    partitions: Vec<Vec<Complex<f32>>>,
+    /// The spectra of the most recent input blocks, one per partition, used as a ring buffer.
+    history: Vec<Vec<Complex<f32>>>,
+    history_pos: usize,
+    /// The previous and current input blocks.
+    input: Vec<f32>,
+    output: Vec<f32>,
+    pos: usize,
+    accum: Vec<Complex<f32>>,
+    scratch: Vec<Complex<f32>>,
+}
+
+impl PartitionedConvolver {
+    pub fn new(ir: &[f32], block_size: usize) -> Self {
+        let block_size = block_size.max(1);
+        let fft_size = 2 * block_size;
+
+        let mut planner = FftPlanner::<f32>::new();
+        let fft = planner.plan_fft_forward(fft_size);
+        let ifft = planner.plan_fft_inverse(fft_size);
+
+        let num_partitions = ir.len().div_ceil(block_size).max(1);
+        let mut partitions = vec![vec![Complex::default(); fft_size]; num_partitions];
+        for (partition, chunk) in partitions.iter_mut().zip(ir.chunks(block_size)) {
+            for (bin, &x) in partition.iter_mut().zip(chunk) {
+                *bin = Complex::new(x, 0.0);
+            }
+            fft.process(partition);
+        }
+
+        let scratch_len = fft
+            .get_inplace_scratch_len()
+            .max(ifft.get_inplace_scratch_len());
+
+        Self {
+            block_size,
+            fft,
+            ifft,
+            partitions,
+            history: vec![vec![Complex::default(); fft_size]; num_partitions],
+            history_pos: 0,
+            input: vec![0.0; fft_size],
+            output: vec![0.0; block_size],
+            pos: 0,
+            accum: vec![Complex::default(); fft_size],
+            scratch: vec![Complex::default(); scratch_len],
+        }
+    }
+
+    /// The delay between the input and the output, in samples.
+    #[inline]
+    pub fn latency(&self) -> usize {
+        self.block_size
+    }
+
+    #[inline]
+    pub fn process(&mut self, input: f32) -> f32 {
+        self.input[self.block_size + self.pos] = input;
+        let out = self.output[self.pos];
+
+        self.pos += 1;
+        if self.pos == self.block_size {
+            self.pos = 0;
+            self.process_block();
+        }
+
+        out
+    }
+
+    fn process_block(&mut self) {
+        let num_partitions = self.partitions.len();
+        let fft_size = 2 * self.block_size;
+
+        let spectrum = &mut self.history[self.history_pos];
+        for (bin, &x) in spectrum.iter_mut().zip(&self.input) {
+            *bin = Complex::new(x, 0.0);
+        }
+        self.fft.process_with_scratch(spectrum, &mut self.scratch);
+
+        // the newest input block meets the first partition, the oldest meets the last
+        self.accum.fill(Complex::default());
+        for (i, partition) in self.partitions.iter().enumerate() {
+            let spectrum = &self.history[(self.history_pos + num_partitions - i) % num_partitions];
+            for ((acc, x), h) in self.accum.iter_mut().zip(spectrum).zip(partition) {
+                *acc += x * h;
+            }
+        }
+        self.ifft
+            .process_with_scratch(&mut self.accum, &mut self.scratch);
+
+        // overlap-save: only the second half is free of circular wraparound
+        let scale = 1.0 / fft_size as f32;
+        for (y, x) in self.output.iter_mut().zip(&self.accum[self.block_size..]) {
+            *y = x.re * scale;
+        }
+
+        self.input.copy_within(self.block_size.., 0);
+        self.history_pos = (self.history_pos + 1) % num_partitions;
+    }
+}
+
+/// The impulse responses of a [`Convolver`].
+#[derive(Clone)]
+pub enum ImpulseResponse {
+    /// One response, applied to each channel separately.
+    Mono(SampleStorage),
+    /// One response per channel.
+    Stereo([SampleStorage; 2]),
+    /// Left-to-left, left-to-right, right-to-left and right-to-right responses.
+    TrueStereo([SampleStorage; 4]),
+}
+
+impl Default for ImpulseResponse {
+    fn default() -> Self {
+        ImpulseResponse::Mono(SampleStorage::default())
+    }
+}
+
+impl ImpulseResponse {
+    /// Loads a 1, 2 or 4 channel file as a mono, stereo or true-stereo response.
+    pub fn load(path: &str) -> Result<Self, ConvolverError> {
+        let channels = SampleStorage::load_channels(path)?;
+        let count = channels.len();
+        match <[SampleStorage; 4]>::try_from(channels) {
+            Ok(channels) => Ok(ImpulseResponse::TrueStereo(channels)),
+            Err(channels) => match <[SampleStorage; 2]>::try_from(channels) {
+                Ok(channels) => Ok(ImpulseResponse::Stereo(channels)),
+                Err(channels) => match <[SampleStorage; 1]>::try_from(channels) {
+                    Ok([channel]) => Ok(ImpulseResponse::Mono(channel)),
+                    Err(_) => Err(ConvolverError::UnsupportedChannels(count)),
+                },
+            },
+        }
+    }
+
+    fn resample(&mut self, sample_rate: f32) {
+        let storages: &mut [SampleStorage] = match self {
+            ImpulseResponse::Mono(ir) => std::slice::from_mut(ir),
+            ImpulseResponse::Stereo(irs) => irs,
+            ImpulseResponse::TrueStereo(irs) => irs,
+        };
+        for storage in storages {
+            storage.resample(sample_rate);
+        }
+    }
+
+    /// The (input channel, output channel, response) triples to convolve.
+    fn routes(&self) -> Vec<(usize, usize, &SampleStorage)> {
+        match self {
+            ImpulseResponse::Mono(ir) => vec![(0, 0, ir), (1, 1, ir)],
+            ImpulseResponse::Stereo([l, r]) => vec![(0, 0, l), (1, 1, r)],
+            ImpulseResponse::TrueStereo([ll, lr, rl, rr]) => {
+                vec![(0, 0, ll), (0, 1, lr), (1, 0, rl), (1, 1, rr)]
+            }
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct ConvolverRoute {
+    input: usize,
+    output: usize,
+    engine: PartitionedConvolver,
+}
+
+/// Convolves a stereo signal with a recorded impulse response, such as a room or a guitar cabinet.
+///
+/// The wet signal lags by one graph block. `mix` crossfades from the dry input (0) to the wet signal (1).
+#[processor(allocate = convolver_allocate)]
+#[allow(unused)]
+pub fn convolver(
+    #[state] ir: &mut ImpulseResponse,
+    #[state] routes: &mut Vec<ConvolverRoute>,
+    #[input] in_l: &f32,
+    #[input] in_r: &f32,
+    #[input] mix: &f32,
+    #[output] out_l: &mut f32,
+    #[output] out_r: &mut f32,
+) -> ProcResult<()> {
+    let inputs = [*in_l, *in_r];
+    let mut wet = [0.0; 2];
+    for route in routes.iter_mut() {
+        wet[route.output] += route.engine.process(inputs[route.input]);
+    }
+
+    let mix = mix.clamp(0.0, 1.0);
+    *out_l = in_l + (wet[0] - in_l) * mix;
+    *out_r = in_r + (wet[1] - in_r) * mix;
+
+    Ok(())
+}
+
+fn convolver_allocate(proc: &mut Convolver, sample_rate: f32, block_size: usize) {
+    proc.ir.resample(sample_rate);
+    proc.routes = proc
+        .ir
+        .routes()
+        .into_iter()
+        .map(|(input, output, ir)| ConvolverRoute {
+            input,
+            output,
+            engine: PartitionedConvolver::new(ir.as_slice(), block_size),
+        })
+        .collect();
+}
+
+impl Convolver {
+    pub fn new(ir: ImpulseResponse) -> Self {
+        Self {
+            ir,
+            ..Default::default()
+        }
+    }
+
+    /// Loads a 1, 2 or 4 channel impulse response file.
+    pub fn load(path: &str) -> Result<Self, ConvolverError> {
+        Ok(Self::new(ImpulseResponse::load(path)?))
+    }
+
+    /// Loads a true-stereo response from four mono files.
+    pub fn load_true_stereo(
+        ll: &str,
+        lr: &str,
+        rl: &str,
+        rr: &str,
+    ) -> Result<Self, ConvolverError> {
+        Ok(Self::new(ImpulseResponse::TrueStereo([
+            SampleStorage::load(ll)?,
+            SampleStorage::load(lr)?,
+            SampleStorage::load(rl)?,
+            SampleStorage::load(rr)?,
+        ])))
+    }
+}
+
+impl Default for Convolver {
+    fn default() -> Self {
+        Self {
+            ir: ImpulseResponse::default(),
+            routes: Vec::new(),
+            in_l: 0.0,
+            in_r: 0.0,
+            mix: 1.0,
+        }
+    }
+}
@@ -0,0 +1,382 @@
+use std::f64::consts::PI;
+
+use raug::prelude::*;
+use rustfft::num_complex::Complex64;
+
+use super::BiquadState;
+
+/// The analog prototype response a filter cascade is designed from.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum FilterDesign {
+    /// Maximally flat passband, -3 dB at the cutoff.
+    #[default]
+    Butterworth,
+    /// Two Butterworth filters of half the order in series, -6 dB at the cutoff.
+    /// Matching lowpass and highpass outputs sum to a flat magnitude response, which makes this the
+    /// usual choice for crossovers.
+    LinkwitzRiley,
+    /// Equiripple passband with `ripple` dB of ripple. The cutoff is the edge of the ripple band.
+    ChebyshevI { ripple: f32 },
+    /// Flat passband and an equiripple stopband at least `attenuation` dB down.
+    /// The cutoff is where the stopband begins.
+    ChebyshevII { attenuation: f32 },
+    /// Maximally flat group delay, -3 dB at the cutoff.
+    Bessel,
+}
+
+impl FilterDesign {
+    /// The gain at the reference point of the passband (DC for a lowpass).
+    fn passband_gain(self) -> f64 {
+        match self {
+            // even-order Chebyshev filters start at the bottom of their ripple
+            FilterDesign::ChebyshevI { ripple } => 10f64.powf(-ripple.max(0.01) as f64 / 20.0),
+            _ => 1.0,
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum CascadeMode {
+    #[default]
+    Lowpass,
+    Highpass,
+    Bandpass,
+}
+
+/// One second-order section of an analog lowpass prototype with a cutoff of 1 rad/s.
+#[derive(Debug, Clone, Copy)]
+pub struct AnalogSection {
+    poles: [Complex64; 2],
+    /// `None` if both zeros are at infinity.
+    zeros: Option<[Complex64; 2]>,
+}
+
+/// Groups poles into conjugate pairs, pairing up any real poles with each other.
+fn pair(poles: &[Complex64]) -> Vec<[Complex64; 2]> {
+    let mut pairs: Vec<_> = poles
+        .iter()
+        .filter(|p| p.im > 1e-9)
+        .map(|p| [*p, p.conj()])
+        .collect();
+    let real: Vec<_> = poles
+        .iter()
+        .filter(|p| p.im.abs() <= 1e-9)
+        .map(|p| Complex64::new(p.re, 0.0))
+        .collect();
+    pairs.extend(real.chunks_exact(2).map(|p| [p[0], p[1]]));
+    pairs
+}
+
+fn butterworth_poles(order: usize) -> Vec<Complex64> {
+    let n = order as f64;
+    (0..order)
+        .map(|k| Complex64::from_polar(1.0, PI * (2.0 * k as f64 + n + 1.0) / (2.0 * n)))
+        .collect()
+}
+
+fn chebyshev_poles(order: usize, ripple: f32) -> Vec<Complex64> {
+    let n = order as f64;
+    let eps = (10f64.powf(ripple.max(0.01) as f64 / 10.0) - 1.0).sqrt();
+    let mu = (1.0 / eps).asinh() / n;
+    (0..order)
+        .map(|k| {
+            let theta = PI * (2.0 * k as f64 + 1.0) / (2.0 * n);
+            Complex64::new(-mu.sinh() * theta.sin(), mu.cosh() * theta.cos())
+        })
+        .collect()
+}
+
+fn bessel_poles(order: usize) -> Vec<Complex64> {
+    // coefficients of the reverse Bessel polynomial, from the highest power down
+    let mut coeffs = vec![1.0f64; order + 1];
+    for k in (0..order).rev() {
+        let (n, k) = (order as f64, k as f64);
+        coeffs[order - k as usize] =
+            coeffs[order - k as usize - 1] * (2.0 * n - k) * (k + 1.0) / (2.0 * (n - k));
+    }
+    let eval = |s: Complex64| {
+        coeffs
+            .iter()
+            .fold(Complex64::default(), |acc, c| acc * s + c)
+    };
+
+    // Durand-Kerner root finding
+    let mut roots: Vec<_> = (0..order)
+        .map(|i| Complex64::new(0.4, 0.9).powu(i as u32))
+        .collect();
+    for _ in 0..500 {
+        for i in 0..order {
+            let denom = (0..order)
+                .filter(|&j| j != i)
+                .fold(Complex64::new(1.0, 0.0), |acc, j| {
+                    acc * (roots[i] - roots[j])
+                });
+            let step = eval(roots[i]) / denom;
+            roots[i] -= step;
+        }
+    }
+
+    // rescale so the response is -3 dB at 1 rad/s
+    let dc = coeffs[order];
+    let magnitude = |w: f64| dc / eval(Complex64::new(0.0, w)).norm();
+    let (mut lo, mut hi) = (0.0, 2.0 * order as f64 + 2.0);
+    for _ in 0..100 {
+        let mid = 0.5 * (lo + hi);
+        if magnitude(mid) > std::f64::consts::FRAC_1_SQRT_2 {
+            lo = mid;
+        } else {
+            hi = mid;
+        }
+    }
+    roots.iter().map(|p| p / lo).collect()
+}
+
+/// Designs the analog lowpass prototype of the given (even) order.
+pub fn analog_prototype(design: FilterDesign, order: usize) -> Vec<AnalogSection> {
+    let all_pole = |poles: Vec<Complex64>| {
+        pair(&poles)
+            .into_iter()
+            .map(|poles| AnalogSection { poles, zeros: None })
+            .collect()
+    };
+
+    match design {
+        FilterDesign::Butterworth => all_pole(butterworth_poles(order)),
+        FilterDesign::LinkwitzRiley => {
+            let mut poles = butterworth_poles(order / 2);
+            poles.extend(poles.clone());
+            all_pole(poles)
+        }
+        FilterDesign::ChebyshevI { ripple } => all_pole(chebyshev_poles(order, ripple)),
+        FilterDesign::ChebyshevII { attenuation } => {
+            let n = order as f64;
+            let eps = 1.0 / (10f64.powf(attenuation.max(1.0) as f64 / 10.0) - 1.0).sqrt();
+            let mu = (1.0 / eps).asinh() / n;
+            (0..order / 2)
+                .map(|k| {
+                    let theta = PI * (2.0 * k as f64 + 1.0) / (2.0 * n);
+                    let pole =
+                        Complex64::new(-mu.sinh() * theta.sin(), mu.cosh() * theta.cos()).inv();
+                    let zero = Complex64::new(0.0, 1.0 / theta.cos());
+                    AnalogSection {
+                        poles: [pole, pole.conj()],
+                        zeros: Some([zero, zero.conj()]),
+                    }
+                })
+                .collect()
+        }
+        FilterDesign::Bessel => all_pole(bessel_poles(order)),
+    }
+}
+
+/// Sets a section's coefficients from its z-plane poles and zeros, scaled to `gain` at the point `at`.
+fn set_section(
+    filter: &mut BiquadState,
+    poles: [Complex64; 2],
+    zeros: [Complex64; 2],
+    at: Complex64,
+    gain: f64,
+) {
+    let b1 = -(zeros[0] + zeros[1]).re;
+    let b2 = (zeros[0] * zeros[1]).re;
+    let a1 = -(poles[0] + poles[1]).re;
+    let a2 = (poles[0] * poles[1]).re;
+
+    let zi = at.inv();
+    let response = (1.0 + zi * (b1 + zi * b2)) / (1.0 + zi * (a1 + zi * a2));
+    let g = gain / response.norm().max(f64::EPSILON);
+
+    filter.b0 = g as f32;
+    filter.b1 = (g * b1) as f32;
+    filter.b2 = (g * b2) as f32;
+    filter.a1 = a1 as f32;
+    filter.a2 = a2 as f32;
+}
+
+/// Writes the coefficients of a digital cascade into `sections`, which must hold one section per
+/// prototype section (two for [`CascadeMode::Bandpass`]). `q` sets the bandwidth of the bandpass mode.
+pub fn design_into(
+    prototype: &[AnalogSection],
+    design: FilterDesign,
+    mode: CascadeMode,
+    cutoff: f32,
+    q: f32,
+    sample_rate: f32,
+    sections: &mut [BiquadState],
+) {
+    let sr = sample_rate as f64;
+    let fs2 = 2.0 * sr;
+    // prewarp so the digital response lands exactly on the requested frequencies
+    let warp = |f: f64| fs2 * (PI * f.clamp(1.0, 0.49 * sr) / sr).tan();
+    let bilinear = |s: Complex64| (fs2 + s) / (fs2 - s);
+    let one = Complex64::new(1.0, 0.0);
+    let mut gain = design.passband_gain();
+
+    match mode {
+        CascadeMode::Lowpass | CascadeMode::Highpass => {
+            let w = warp(cutoff as f64);
+            let (transform, infinite_zero, at): (&dyn Fn(Complex64) -> Complex64, _, _) = match mode
+            {
+                CascadeMode::Lowpass => (&|s| s * w, -one, one),
+                _ => (&|s| w / s, one, -one),
+            };
+
+            for (section, filter) in prototype.iter().zip(sections.iter_mut()) {
+                let poles = section.poles.map(|p| bilinear(transform(p)));
+                let zeros = section
+                    .zeros
+                    .map_or([infinite_zero; 2], |z| z.map(|z| bilinear(transform(z))));
+                set_section(filter, poles, zeros, at, gain);
+                gain = 1.0;
+            }
+        }
+        CascadeMode::Bandpass => {
+            let cutoff = cutoff as f64;
+            let half = 0.5 / q.max(0.01) as f64;
+            let k = (1.0 + half * half).sqrt();
+            let (w1, w2) = (warp(cutoff * (k - half)), warp(cutoff * (k + half)));
+            let w0 = (w1 * w2).sqrt();
+            let bw = w2 - w1;
+            let at = bilinear(Complex64::new(0.0, w0));
+
+            // each lowpass root splits into the two roots of s^2 - p*bw*s + w0^2
+            let split = |p: Complex64| {
+                let pb = p * bw;
+                let d = (pb * pb - 4.0 * w0 * w0).sqrt();
+                [(pb + d) / 2.0, (pb - d) / 2.0]
+            };
+            let split_pair = |pair: [Complex64; 2]| {
+                let [r0, r1] = split(pair[0]);
+                if pair[0].im.abs() > 1e-9 {
+                    [[r0, r0.conj()], [r1, r1.conj()]]
+                } else {
+                    [[r0, r1], split(pair[1])]
+                }
+            };
+
+            for (section, filters) in prototype.iter().zip(sections.chunks_exact_mut(2)) {
+                let poles = split_pair(section.poles);
+                let zeros = section.zeros.map_or([[one, -one]; 2], |z| {
+                    split_pair(z).map(|pair| pair.map(bilinear))
+                });
+                for ((filter, poles), zeros) in filters.iter_mut().zip(poles).zip(zeros) {
+                    set_section(filter, poles.map(bilinear), zeros, at, gain);
+                    gain = 1.0;
+                }
+            }
+        }
+    }
+}
+
+/// Designs a digital filter cascade, rounding `order` up to the next even number.
+pub fn design_cascade(
+    design: FilterDesign,
+    mode: CascadeMode,
+    order: usize,
+    cutoff: f32,
+    q: f32,
+    sample_rate: f32,
+) -> Vec<BiquadState> {
+    let prototype = analog_prototype(design, even_order(order));
+    let mut sections = vec![BiquadState::default(); section_count(&prototype, mode)];
+    design_into(
+        &prototype,
+        design,
+        mode,
+        cutoff,
+        q,
+        sample_rate,
+        &mut sections,
+    );
+    sections
+}
+
+#[inline]
+fn even_order(order: usize) -> usize {
+    (order.max(2) + 1) & !1
+}
+
+#[inline]
+fn section_count(prototype: &[AnalogSection], mode: CascadeMode) -> usize {
+    match mode {
+        CascadeMode::Bandpass => 2 * prototype.len(),
+        _ => prototype.len(),
+    }
+}
+
+/// A high-order filter built from a cascade of second-order sections.
+///
+/// `q` only affects the bandpass mode, where it sets the bandwidth relative to `cutoff`.
+#[processor(allocate = cascade_filter_allocate)]
+pub fn cascade_filter(
+    env: ProcEnv,
+    #[state] design: &mut FilterDesign,
+    #[state] mode: &mut CascadeMode,
+    #[state] prototype: &mut Vec<AnalogSection>,
+    #[state] sections: &mut Vec<BiquadState>,
+    #[state] last_params: &mut [f32; 2],
+    #[input] input: &f32,
+    #[input] cutoff: &f32,
+    #[input] q: &f32,
+    #[output] out: &mut f32,
+) -> ProcResult<()> {
+    if *last_params != [*cutoff, *q] {
+        *last_params = [*cutoff, *q];
+        design_into(
+            prototype,
+            *design,
+            *mode,
+            *cutoff,
+            *q,
+            env.sample_rate,
+            sections,
+        );
+    }
+
+    *out = sections
+        .iter_mut()
+        .fold(*input, |x, section| section.process(x));
+
+    Ok(())
+}
+
+fn cascade_filter_allocate(proc: &mut CascadeFilter, _sample_rate: f32, _block_size: usize) {
+    proc.sections = vec![BiquadState::default(); section_count(&proc.prototype, proc.mode)];
+    proc.last_params = [f32::NAN; 2];
+}
+
+impl CascadeFilter {
+    /// Constructs a new [`CascadeFilter`], rounding `order` up to the next even number.
+    pub fn new(design: FilterDesign, mode: CascadeMode, order: usize) -> Self {
+        let prototype = analog_prototype(design, even_order(order));
+        let sections = vec![BiquadState::default(); section_count(&prototype, mode)];
+        Self {
+            design,
+            mode,
+            prototype,
+            sections,
+            last_params: [f32::NAN; 2],
+            input: 0.0,
+            cutoff: 1000.0,
+            q: 1.0,
+        }
+    }
+
+    pub fn lowpass(design: FilterDesign, order: usize) -> Self {
+        Self::new(design, CascadeMode::Lowpass, order)
+    }
+
+    pub fn highpass(design: FilterDesign, order: usize) -> Self {
+        Self::new(design, CascadeMode::Highpass, order)
+    }
+
+    pub fn bandpass(design: FilterDesign, order: usize) -> Self {
+        Self::new(design, CascadeMode::Bandpass, order)
+    }
+}
+
+impl Default for CascadeFilter {
+    fn default() -> Self {
+        Self::new(FilterDesign::Butterworth, CascadeMode::Lowpass, 4)
+    }
+}
@@ -49,3 +49,29 @@ impl Default for PeakLimiter {
         }
     }
 }
+
+/// A peak envelope follower with separate attack and release smoothing.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct EnvelopeFollower {
+    pub envelope: f32,
+}
+
+impl EnvelopeFollower {
+    /// Converts a time constant in seconds to a smoothing coefficient for [`EnvelopeFollower::process`].
+    #[inline]
+    pub fn coefficient(time: f32, sample_rate: f32) -> f32 {
+        (-1.0 / (time.max(1e-4) * sample_rate)).exp()
+    }
+
+    #[inline]
+    pub fn process(&mut self, input: f32, attack: f32, release: f32) -> f32 {
+        let level = input.abs();
+        let coeff = if level > self.envelope {
+            attack
+        } else {
+            release
+        };
+        self.envelope = level + (self.envelope - level) * coeff;
+        self.envelope
+    }
+}
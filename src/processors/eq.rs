@@ -0,0 +1,426 @@
+use std::sync::{Arc, Mutex};
+
+use crossbeam_channel::{Receiver, Sender};
+use raug::prelude::*;
+use thiserror::Error;
+
+use super::{
+    AnalogSection, BiquadError, BiquadState, CascadeMode, FilterDesign, analog_prototype,
+    design_into,
+};
+
+pub const MAX_EQ_BANDS: usize = 32;
+/// The steepest cut band slope, in dB/octave.
+pub const MAX_EQ_SLOPE: f32 = 96.0;
+const MAX_EQ_BAND_SECTIONS: usize = (MAX_EQ_SLOPE as usize) / 12;
+
+#[derive(Error, Debug)]
+pub enum EqError {
+    #[error("Failed to send EQ command")]
+    SendError,
+    #[error("Band index out of bounds: {0}")]
+    BandOutOfBounds(usize),
+    #[error("Too many bands (at most {MAX_EQ_BANDS})")]
+    TooManyBands,
+    #[error("Invalid EQ band: {0:?}")]
+    InvalidBand(EqBand),
+    #[error(transparent)]
+    Biquad(#[from] BiquadError),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EqBandType {
+    LowShelf,
+    HighShelf,
+    Peaking,
+    /// A Butterworth highpass. `slope` is in dB/octave, rounded to a multiple of 12 up to
+    /// [`MAX_EQ_SLOPE`].
+    LowCut {
+        slope: f32,
+    },
+    /// A Butterworth lowpass. `slope` is in dB/octave, rounded to a multiple of 12 up to
+    /// [`MAX_EQ_SLOPE`].
+    HighCut {
+        slope: f32,
+    },
+}
+
+/// The settings of one [`ParametricEq`] band. `gain` is in dB. The cut types ignore `gain` and `q`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EqBand {
+    pub kind: EqBandType,
+    pub frequency: f32,
+    pub gain: f32,
+    pub q: f32,
+}
+
+impl EqBand {
+    pub const fn low_shelf(frequency: f32, gain: f32, q: f32) -> Self {
+        Self {
+            kind: EqBandType::LowShelf,
+            frequency,
+            gain,
+            q,
+        }
+    }
+
+    pub const fn high_shelf(frequency: f32, gain: f32, q: f32) -> Self {
+        Self {
+            kind: EqBandType::HighShelf,
+            frequency,
+            gain,
+            q,
+        }
+    }
+
+    pub const fn peaking(frequency: f32, gain: f32, q: f32) -> Self {
+        Self {
+            kind: EqBandType::Peaking,
+            frequency,
+            gain,
+            q,
+        }
+    }
+
+    pub const fn low_cut(frequency: f32, slope: f32) -> Self {
+        Self {
+            kind: EqBandType::LowCut { slope },
+            frequency,
+            gain: 0.0,
+            q: std::f32::consts::FRAC_1_SQRT_2,
+        }
+    }
+
+    pub const fn high_cut(frequency: f32, slope: f32) -> Self {
+        Self {
+            kind: EqBandType::HighCut { slope },
+            frequency,
+            gain: 0.0,
+            q: std::f32::consts::FRAC_1_SQRT_2,
+        }
+    }
+
+    /// Whether every parameter of this band is finite.
+    pub fn is_valid(&self) -> bool {
+        let slope = match self.kind {
+            EqBandType::LowCut { slope } | EqBandType::HighCut { slope } => slope,
+            _ => 0.0,
+        };
+        self.frequency.is_finite()
+            && self.gain.is_finite()
+            && self.q.is_finite()
+            && slope.is_finite()
+    }
+
+    /// The Butterworth order of a cut band, or `None` for the other types.
+    fn cut_order(&self) -> Option<usize> {
+        match self.kind {
+            EqBandType::LowCut { slope } | EqBandType::HighCut { slope } => {
+                Some(2 * ((slope.min(MAX_EQ_SLOPE) / 12.0).round() as usize).max(1))
+            }
+            _ => None,
+        }
+    }
+
+    /// Writes this band's coefficients into `sections`, resizing it if the band needs more or fewer.
+    pub fn design(
+        &self,
+        sections: &mut Vec<BiquadState>,
+        sample_rate: f32,
+    ) -> Result<(), BiquadError> {
+        let prototype = self
+            .cut_order()
+            .map(|order| analog_prototype(FilterDesign::Butterworth, order));
+        self.design_with(
+            prototype.as_deref().unwrap_or_default(),
+            sections,
+            sample_rate,
+        )
+    }
+
+    /// Like [`EqBand::design`], but with the Butterworth prototype of a cut band supplied by the caller,
+    /// so that nothing is allocated as long as `sections` has room.
+    fn design_with(
+        &self,
+        prototype: &[AnalogSection],
+        sections: &mut Vec<BiquadState>,
+        sample_rate: f32,
+    ) -> Result<(), BiquadError> {
+        let frequency = self.frequency.clamp(1.0, 0.49 * sample_rate);
+        let q = self.q.max(0.01);
+        // RBJ shelves and peaks take the square root of the linear gain
+        let amp = 10f32.powf(self.gain / 40.0);
+
+        let mode = match self.kind {
+            EqBandType::LowCut { .. } => CascadeMode::Highpass,
+            EqBandType::HighCut { .. } => CascadeMode::Lowpass,
+            kind => {
+                sections.resize(1, BiquadState::default());
+                let section = &mut sections[0];
+                return match kind {
+                    EqBandType::LowShelf => section.lowshelf(frequency, q, amp, sample_rate),
+                    EqBandType::HighShelf => section.highshelf(frequency, q, amp, sample_rate),
+                    _ => section.peaking(frequency, q, amp, sample_rate),
+                };
+            }
+        };
+
+        sections.resize(prototype.len(), BiquadState::default());
+        design_into(
+            prototype,
+            FilterDesign::Butterworth,
+            mode,
+            frequency,
+            q,
+            sample_rate,
+            sections,
+        );
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum EqCommand {
+    Add(EqBand),
+    Set(usize, EqBand),
+    Remove(usize),
+    Clear,
+}
+
+/// Edits the bands of a [`ParametricEq`] from outside the audio graph.
+#[derive(Debug, Clone)]
+pub struct EqHandle {
+    tx: Sender<EqCommand>,
+    rx: Receiver<EqCommand>,
+    bands: Arc<Mutex<Vec<EqBand>>>,
+}
+
+impl EqHandle {
+    fn send(&self, command: EqCommand) -> Result<(), EqError> {
+        if self.tx.try_send(command).is_err() {
+            return Err(EqError::SendError);
+        }
+        Ok(())
+    }
+
+    pub fn add_band(&self, band: EqBand) -> Result<(), EqError> {
+        if !band.is_valid() {
+            return Err(EqError::InvalidBand(band));
+        }
+        let mut bands = self.bands.lock().unwrap();
+        if bands.len() >= MAX_EQ_BANDS {
+            return Err(EqError::TooManyBands);
+        }
+        self.send(EqCommand::Add(band))?;
+        bands.push(band);
+        Ok(())
+    }
+
+    pub fn set_band(&self, index: usize, band: EqBand) -> Result<(), EqError> {
+        if !band.is_valid() {
+            return Err(EqError::InvalidBand(band));
+        }
+        let mut bands = self.bands.lock().unwrap();
+        let Some(b) = bands.get_mut(index) else {
+            return Err(EqError::BandOutOfBounds(index));
+        };
+        self.send(EqCommand::Set(index, band))?;
+        *b = band;
+        Ok(())
+    }
+
+    pub fn remove_band(&self, index: usize) -> Result<(), EqError> {
+        let mut bands = self.bands.lock().unwrap();
+        if index >= bands.len() {
+            return Err(EqError::BandOutOfBounds(index));
+        }
+        self.send(EqCommand::Remove(index))?;
+        bands.remove(index);
+        Ok(())
+    }
+
+    pub fn clear(&self) -> Result<(), EqError> {
+        let mut bands = self.bands.lock().unwrap();
+        self.send(EqCommand::Clear)?;
+        bands.clear();
+        Ok(())
+    }
+
+    /// The bands as of the last command sent through any clone of this handle.
+    pub fn bands(&self) -> Vec<EqBand> {
+        self.bands.lock().unwrap().clone()
+    }
+
+    /// Designs every band's sections at `sample_rate`, for drawing the combined response with
+    /// [`FrequencyResponse`](super::FrequencyResponse). The processor itself can't be queried once it
+    /// is part of a graph.
+    pub fn sections(&self, sample_rate: f32) -> Result<Vec<BiquadState>, BiquadError> {
+        let mut sections = Vec::new();
+        for band in self.bands().iter() {
+            let mut band_sections = Vec::new();
+            band.design(&mut band_sections, sample_rate)?;
+            sections.extend(band_sections);
+        }
+        Ok(sections)
+    }
+}
+
+#[derive(Clone)]
+pub struct EqBandState {
+    band: EqBand,
+    sections: Vec<BiquadState>,
+}
+
+impl EqBandState {
+    /// Designs `band` into this slot without allocating, clearing the filter memory when the slot is
+    /// `fresh` or the band's type or slope changed, since the old memory no longer matches the sections.
+    fn update(
+        &mut self,
+        band: EqBand,
+        prototypes: &[Vec<AnalogSection>],
+        sample_rate: f32,
+        fresh: bool,
+    ) -> Result<(), BiquadError> {
+        let reset = fresh || band.kind != self.band.kind;
+        self.band = band;
+        band.design_with(
+            prototype_for(&band, prototypes),
+            &mut self.sections,
+            sample_rate,
+        )?;
+        if reset {
+            for section in self.sections.iter_mut() {
+                section.prev_in = [0.0; 2];
+                section.prev_out = [0.0; 2];
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The Butterworth prototypes for every cut order a band can have, indexed by `order / 2 - 1`.
+fn cut_prototypes() -> Vec<Vec<AnalogSection>> {
+    (1..=MAX_EQ_BAND_SECTIONS)
+        .map(|sections| analog_prototype(FilterDesign::Butterworth, 2 * sections))
+        .collect()
+}
+
+#[inline]
+fn prototype_for<'a>(band: &EqBand, prototypes: &'a [Vec<AnalogSection>]) -> &'a [AnalogSection] {
+    band.cut_order()
+        .map_or(&[], |order| prototypes[order / 2 - 1].as_slice())
+}
+
+impl Default for EqBandState {
+    fn default() -> Self {
+        Self {
+            band: EqBand::peaking(1000.0, 0.0, 1.0),
+            sections: Vec::with_capacity(MAX_EQ_BAND_SECTIONS),
+        }
+    }
+}
+
+/// A multi-band parametric equalizer.
+///
+/// Bands run in series and can be added, changed and removed while the graph is running through an
+/// [`EqHandle`]. Room for [`MAX_EQ_BANDS`] bands is allocated up front, so editing them never allocates
+/// or frees memory on the audio thread.
+#[processor]
+pub fn parametric_eq(
+    env: ProcEnv,
+    #[state] bands: &mut Vec<EqBandState>,
+    #[state] active: &mut usize,
+    #[state] handle: &mut EqHandle,
+    #[state] prototypes: &mut Vec<Vec<AnalogSection>>,
+    #[state] designed_rate: &mut f32,
+    #[input] input: &f32,
+    #[output] out: &mut f32,
+) -> ProcResult<()> {
+    if *designed_rate != env.sample_rate {
+        *designed_rate = env.sample_rate;
+        for band in bands.iter_mut().take(*active) {
+            band.band
+                .design_with(
+                    prototype_for(&band.band, prototypes),
+                    &mut band.sections,
+                    env.sample_rate,
+                )
+                .map_err(ProcessorError::new)?;
+        }
+    }
+
+    while let Ok(command) = handle.rx.try_recv() {
+        match command {
+            EqCommand::Add(band) => {
+                let Some(state) = bands.get_mut(*active) else {
+                    return Err(ProcessorError::new(EqError::TooManyBands));
+                };
+                *active += 1;
+                state
+                    .update(band, prototypes, env.sample_rate, true)
+                    .map_err(ProcessorError::new)?;
+            }
+            EqCommand::Set(index, band) => {
+                if index >= *active {
+                    return Err(ProcessorError::new(EqError::BandOutOfBounds(index)));
+                }
+                bands[index]
+                    .update(band, prototypes, env.sample_rate, false)
+                    .map_err(ProcessorError::new)?;
+            }
+            EqCommand::Remove(index) => {
+                if index >= *active {
+                    return Err(ProcessorError::new(EqError::BandOutOfBounds(index)));
+                }
+                // keep the removed slot, and its allocation, for the next band to be added
+                bands[index..*active].rotate_left(1);
+                *active -= 1;
+            }
+            EqCommand::Clear => *active = 0,
+        }
+    }
+
+    *out = bands
+        .iter_mut()
+        .take(*active)
+        .flat_map(|band| band.sections.iter_mut())
+        .fold(*input, |x, section| section.process(x));
+
+    Ok(())
+}
+
+impl ParametricEq {
+    pub fn new(bands: &[EqBand]) -> Self {
+        let bands = &bands[..bands.len().min(MAX_EQ_BANDS)];
+        // a bounded channel never allocates or frees memory when the audio thread receives from it
+        let (tx, rx) = crossbeam_channel::bounded(4 * MAX_EQ_BANDS);
+        // not `vec![...]`, which would clone away each slot's reserved capacity
+        let mut states: Vec<_> = (0..MAX_EQ_BANDS).map(|_| EqBandState::default()).collect();
+        for (state, band) in states.iter_mut().zip(bands) {
+            state.band = *band;
+        }
+        Self {
+            bands: states,
+            active: bands.len(),
+            handle: EqHandle {
+                tx,
+                rx,
+                bands: Arc::new(Mutex::new(bands.to_vec())),
+            },
+            prototypes: cut_prototypes(),
+            designed_rate: 0.0,
+            input: 0.0,
+        }
+    }
+
+    /// Returns a handle for editing the bands once the equalizer is part of a graph.
+    pub fn handle(&self) -> EqHandle {
+        self.handle.clone()
+    }
+}
+
+impl Default for ParametricEq {
+    fn default() -> Self {
+        Self::new(&[])
+    }
+}
@@ -1,5 +1,8 @@
 use raug::prelude::*;
 use std::f32::consts::PI;
+use thiserror::Error;
+
+use super::DelayLine;
 
 #[processor]
 pub fn lowpass1(
@@ -31,6 +34,66 @@ pub fn highpass1(
     Ok(())
 }
 
+/// A zero-delay-feedback (topology-preserving transform) state variable filter.
+///
+/// All five responses are computed at once, and the filter stays stable when `cutoff` and `q` are
+/// modulated at audio rate.
+#[processor]
+pub fn svf(
+    env: ProcEnv,
+    #[state] ic1eq: &mut f32,
+    #[state] ic2eq: &mut f32,
+    #[input] input: &f32,
+    #[input] cutoff: &f32,
+    #[input] q: &f32,
+    #[output] lowpass: &mut f32,
+    #[output] highpass: &mut f32,
+    #[output] bandpass: &mut f32,
+    #[output] notch: &mut f32,
+    #[output] peak: &mut f32,
+) -> ProcResult<()> {
+    let cutoff = cutoff.clamp(0.0, 0.49 * env.sample_rate);
+    let g = (PI * cutoff / env.sample_rate).tan();
+    let k = 1.0 / q.max(0.01);
+
+    let a1 = 1.0 / (1.0 + g * (g + k));
+    let a2 = g * a1;
+    let a3 = g * a2;
+
+    let v3 = input - *ic2eq;
+    let v1 = a1 * *ic1eq + a2 * v3;
+    let v2 = *ic2eq + a2 * *ic1eq + a3 * v3;
+
+    *ic1eq = 2.0 * v1 - *ic1eq;
+    *ic2eq = 2.0 * v2 - *ic2eq;
+
+    *lowpass = v2;
+    *bandpass = v1;
+    *highpass = input - k * v1 - v2;
+    *notch = input - k * v1;
+    *peak = v2 - *highpass;
+
+    Ok(())
+}
+
+impl Default for Svf {
+    fn default() -> Self {
+        Self {
+            ic1eq: 0.0,
+            ic2eq: 0.0,
+            input: 0.0,
+            cutoff: 1000.0,
+            q: std::f32::consts::FRAC_1_SQRT_2,
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum BiquadError {
+    #[error("Invalid biquad coefficients: a0 is {0}")]
+    InvalidCoefficients(f32),
+}
+
 #[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
 pub enum BiquadMode {
     #[default]
@@ -77,18 +140,47 @@ pub struct BiquadState {
 }
 
 impl BiquadState {
+    /// Divides every coefficient by `a0`. If `a0` is zero or not finite, the section is muted instead.
     #[inline]
-    fn normalize(&mut self, a0: f32) {
-        assert_ne!(a0, 0.0, "a0 cannot be zero");
+    fn normalize(&mut self, a0: f32) -> Result<(), BiquadError> {
+        if a0 == 0.0 || !a0.is_finite() {
+            self.a1 = 0.0;
+            self.a2 = 0.0;
+            self.b0 = 0.0;
+            self.b1 = 0.0;
+            self.b2 = 0.0;
+            return Err(BiquadError::InvalidCoefficients(a0));
+        }
         self.a1 /= a0;
         self.a2 /= a0;
         self.b0 /= a0;
         self.b1 /= a0;
         self.b2 /= a0;
+        Ok(())
+    }
+
+    #[inline]
+    pub fn process(&mut self, input: f32) -> f32 {
+        let out = self.b0 * input + self.b1 * self.prev_in[0] + self.b2 * self.prev_in[1]
+            - self.a1 * self.prev_out[0]
+            - self.a2 * self.prev_out[1];
+
+        self.prev_in[1] = self.prev_in[0];
+        self.prev_in[0] = input;
+        self.prev_out[1] = self.prev_out[0];
+        self.prev_out[0] = out;
+
+        out
     }
 
     #[inline]
-    pub fn update(&mut self, cutoff: f32, q: f32, amp: f32, sample_rate: f32) {
+    pub fn update(
+        &mut self,
+        cutoff: f32,
+        q: f32,
+        amp: f32,
+        sample_rate: f32,
+    ) -> Result<(), BiquadError> {
         let cutoff = cutoff.clamp(0.0, sample_rate / 2.0); // must be > 0 and < nyquist
         let q = q.max(0.001); // must be > 0
         let amp = amp.max(0.001); // must be > 0
@@ -104,7 +196,7 @@ impl BiquadState {
         }
     }
 
-    pub fn lowpass(&mut self, cutoff: f32, q: f32, sample_rate: f32) {
+    pub fn lowpass(&mut self, cutoff: f32, q: f32, sample_rate: f32) -> Result<(), BiquadError> {
         let BiquadCommon { cos_omega, alpha } = BiquadCommon::new(cutoff, q, sample_rate);
 
         let a0 = 1.0 + alpha;
@@ -114,10 +206,10 @@ impl BiquadState {
         self.a1 = -2.0 * cos_omega;
         self.a2 = 1.0 - alpha;
 
-        self.normalize(a0);
+        self.normalize(a0)
     }
 
-    pub fn highpass(&mut self, cutoff: f32, q: f32, sample_rate: f32) {
+    pub fn highpass(&mut self, cutoff: f32, q: f32, sample_rate: f32) -> Result<(), BiquadError> {
         let BiquadCommon { cos_omega, alpha } = BiquadCommon::new(cutoff, q, sample_rate);
 
         let a0 = 1.0 + alpha;
@@ -127,10 +219,10 @@ impl BiquadState {
         self.a1 = -2.0 * cos_omega;
         self.a2 = 1.0 - alpha;
 
-        self.normalize(a0);
+        self.normalize(a0)
     }
 
-    pub fn bandpass(&mut self, cutoff: f32, q: f32, sample_rate: f32) {
+    pub fn bandpass(&mut self, cutoff: f32, q: f32, sample_rate: f32) -> Result<(), BiquadError> {
         let BiquadCommon { cos_omega, alpha } = BiquadCommon::new(cutoff, q, sample_rate);
 
         let a0 = 1.0 + alpha;
@@ -140,10 +232,10 @@ impl BiquadState {
         self.a1 = -2.0 * cos_omega;
         self.a2 = 1.0 - alpha;
 
-        self.normalize(a0);
+        self.normalize(a0)
     }
 
-    pub fn notch(&mut self, cutoff: f32, q: f32, sample_rate: f32) {
+    pub fn notch(&mut self, cutoff: f32, q: f32, sample_rate: f32) -> Result<(), BiquadError> {
         let BiquadCommon { cos_omega, alpha } = BiquadCommon::new(cutoff, q, sample_rate);
 
         let a0 = 1.0 + alpha;
@@ -153,10 +245,10 @@ impl BiquadState {
         self.a1 = -2.0 * cos_omega;
         self.a2 = 1.0 - alpha;
 
-        self.normalize(a0);
+        self.normalize(a0)
     }
 
-    pub fn allpass(&mut self, cutoff: f32, q: f32, sample_rate: f32) {
+    pub fn allpass(&mut self, cutoff: f32, q: f32, sample_rate: f32) -> Result<(), BiquadError> {
         let BiquadCommon { cos_omega, alpha } = BiquadCommon::new(cutoff, q, sample_rate);
 
         let a0 = 1.0 + alpha;
@@ -166,38 +258,56 @@ impl BiquadState {
         self.a1 = -2.0 * cos_omega;
         self.a2 = 1.0 - alpha;
 
-        self.normalize(a0);
+        self.normalize(a0)
     }
 
-    pub fn lowshelf(&mut self, cutoff: f32, q: f32, amp: f32, sample_rate: f32) {
+    pub fn lowshelf(
+        &mut self,
+        cutoff: f32,
+        q: f32,
+        amp: f32,
+        sample_rate: f32,
+    ) -> Result<(), BiquadError> {
         let BiquadCommon { cos_omega, alpha } = BiquadCommon::new(cutoff, q, sample_rate);
         let sqrt_amp = amp.sqrt();
 
-        let a0 = (amp + 1.0) - (amp - 1.0) * cos_omega + 2.0 * sqrt_amp * alpha;
+        let a0 = (amp + 1.0) + (amp - 1.0) * cos_omega + 2.0 * sqrt_amp * alpha;
         self.b0 = amp * ((amp + 1.0) - (amp - 1.0) * cos_omega + 2.0 * sqrt_amp * alpha);
         self.b1 = 2.0 * amp * ((amp - 1.0) - (amp + 1.0) * cos_omega);
         self.b2 = amp * ((amp + 1.0) - (amp - 1.0) * cos_omega - 2.0 * sqrt_amp * alpha);
         self.a1 = -2.0 * ((amp - 1.0) + (amp + 1.0) * cos_omega);
-        self.a2 = (amp + 1.0) - (amp - 1.0) * cos_omega - 2.0 * sqrt_amp * alpha;
+        self.a2 = (amp + 1.0) + (amp - 1.0) * cos_omega - 2.0 * sqrt_amp * alpha;
 
-        self.normalize(a0);
+        self.normalize(a0)
     }
 
-    pub fn highshelf(&mut self, cutoff: f32, q: f32, amp: f32, sample_rate: f32) {
+    pub fn highshelf(
+        &mut self,
+        cutoff: f32,
+        q: f32,
+        amp: f32,
+        sample_rate: f32,
+    ) -> Result<(), BiquadError> {
         let BiquadCommon { cos_omega, alpha } = BiquadCommon::new(cutoff, q, sample_rate);
         let sqrt_amp = amp.sqrt();
 
-        let a0 = (amp + 1.0) + (amp - 1.0) * cos_omega + 2.0 * sqrt_amp * alpha;
+        let a0 = (amp + 1.0) - (amp - 1.0) * cos_omega + 2.0 * sqrt_amp * alpha;
         self.b0 = amp * ((amp + 1.0) + (amp - 1.0) * cos_omega + 2.0 * sqrt_amp * alpha);
         self.b1 = -2.0 * amp * ((amp - 1.0) + (amp + 1.0) * cos_omega);
         self.b2 = amp * ((amp + 1.0) + (amp - 1.0) * cos_omega - 2.0 * sqrt_amp * alpha);
-        self.a1 = -2.0 * ((amp - 1.0) - (amp + 1.0) * cos_omega);
-        self.a2 = (amp + 1.0) + (amp - 1.0) * cos_omega - 2.0 * sqrt_amp * alpha;
+        self.a1 = 2.0 * ((amp - 1.0) - (amp + 1.0) * cos_omega);
+        self.a2 = (amp + 1.0) - (amp - 1.0) * cos_omega - 2.0 * sqrt_amp * alpha;
 
-        self.normalize(a0);
+        self.normalize(a0)
     }
 
-    pub fn peaking(&mut self, cutoff: f32, q: f32, amp: f32, sample_rate: f32) {
+    pub fn peaking(
+        &mut self,
+        cutoff: f32,
+        q: f32,
+        amp: f32,
+        sample_rate: f32,
+    ) -> Result<(), BiquadError> {
         let BiquadCommon { cos_omega, alpha } = BiquadCommon::new(cutoff, q, sample_rate);
 
         let a0 = 1.0 + alpha / amp;
@@ -207,30 +317,58 @@ impl BiquadState {
         self.a1 = -2.0 * cos_omega;
         self.a2 = 1.0 - alpha / amp;
 
-        self.normalize(a0);
+        self.normalize(a0)
     }
 }
 
+/// How long [`Biquad`] crossfades for when its mode is switched, in seconds.
+const BIQUAD_FADE_TIME: f32 = 0.01;
+
+/// A second-order filter whose response is chosen by `mode`.
+///
+/// Switching `mode` while running restarts the filter and crossfades out of the old response.
 #[processor(derive(Default))]
 pub fn biquad(
     env: ProcEnv,
     #[state] state: &mut BiquadState,
+    #[state] last_params: &mut Option<[f32; 4]>,
+    #[state] fade_from: &mut BiquadState,
+    #[state] fade: &mut f32,
     #[input] input: &f32,
     #[input] cutoff: &f32,
     #[input] q: &f32,
     #[input] amp: &f32,
+    #[input] mode: &BiquadMode,
     #[output] out: &mut f32,
 ) -> ProcResult<()> {
-    state.update(*cutoff, *q, *amp, env.sample_rate);
+    if *mode != state.mode {
+        if last_params.is_some() {
+            // fade out the old response while the new one starts from silence
+            *fade_from = *state;
+            *fade = 1.0;
+        }
+        *state = BiquadState {
+            mode: *mode,
+            ..Default::default()
+        };
+        *last_params = None;
+    }
 
-    *out = state.b0 * input + state.b1 * state.prev_in[0] + state.b2 * state.prev_in[1]
-        - state.a1 * state.prev_out[0]
-        - state.a2 * state.prev_out[1];
+    // only redesign the filter when its parameters actually change
+    let params = [*cutoff, *q, *amp, env.sample_rate];
+    if *last_params != Some(params) {
+        state
+            .update(*cutoff, *q, *amp, env.sample_rate)
+            .map_err(ProcessorError::new)?;
+        *last_params = Some(params);
+    }
+    *out = state.process(*input);
 
-    state.prev_in[1] = state.prev_in[0];
-    state.prev_in[0] = *input;
-    state.prev_out[1] = state.prev_out[0];
-    state.prev_out[0] = *out;
+    if *fade > 0.0 {
+        let old = fade_from.process(*input);
+        *out += (old - *out) * *fade;
+        *fade = (*fade - 1.0 / (BIQUAD_FADE_TIME * env.sample_rate)).max(0.0);
+    }
 
     Ok(())
 }
@@ -242,6 +380,7 @@ impl Biquad {
                 mode: BiquadMode::Lowpass,
                 ..Default::default()
             },
+            mode: BiquadMode::Lowpass,
             ..Default::default()
         }
     }
@@ -252,6 +391,7 @@ impl Biquad {
                 mode: BiquadMode::Highpass,
                 ..Default::default()
             },
+            mode: BiquadMode::Highpass,
             ..Default::default()
         }
     }
@@ -262,6 +402,7 @@ impl Biquad {
                 mode: BiquadMode::Bandpass,
                 ..Default::default()
             },
+            mode: BiquadMode::Bandpass,
             ..Default::default()
         }
     }
@@ -272,6 +413,7 @@ impl Biquad {
                 mode: BiquadMode::Notch,
                 ..Default::default()
             },
+            mode: BiquadMode::Notch,
             ..Default::default()
         }
     }
@@ -282,6 +424,7 @@ impl Biquad {
                 mode: BiquadMode::Allpass,
                 ..Default::default()
             },
+            mode: BiquadMode::Allpass,
             ..Default::default()
         }
     }
@@ -292,6 +435,7 @@ impl Biquad {
                 mode: BiquadMode::LowShelf,
                 ..Default::default()
             },
+            mode: BiquadMode::LowShelf,
             ..Default::default()
         }
     }
@@ -302,6 +446,7 @@ impl Biquad {
                 mode: BiquadMode::HighShelf,
                 ..Default::default()
             },
+            mode: BiquadMode::HighShelf,
             ..Default::default()
         }
     }
@@ -312,7 +457,162 @@ impl Biquad {
                 mode: BiquadMode::Peaking,
                 ..Default::default()
             },
+            mode: BiquadMode::Peaking,
             ..Default::default()
         }
     }
 }
+
+/// A transistor ladder lowpass filter, after the classic Moog design.
+///
+/// Four saturating one-pole stages sit inside a global feedback loop that is solved without a unit
+/// delay, so `cutoff` can be swept quickly without the filter going out of tune. `resonance` runs from
+/// 0 to 1, and the filter self-oscillates above roughly 0.9. `drive` pushes the signal harder
+/// into the stages' tanh saturation, and `two_pole` taps the output after the second stage for a
+/// gentler 12 dB/octave slope.
+#[processor]
+pub fn ladder_filter(
+    env: ProcEnv,
+    #[state] stages: &mut [f32; 4],
+    #[input] input: &f32,
+    #[input] cutoff: &f32,
+    #[input] resonance: &f32,
+    #[input] drive: &f32,
+    #[input] two_pole: &bool,
+    #[output] out: &mut f32,
+) -> ProcResult<()> {
+    let cutoff = cutoff.clamp(1.0, 0.49 * env.sample_rate);
+    let g = (PI * cutoff / env.sample_rate).tan();
+    let a = g / (1.0 + g);
+    let k = 4.5 * resonance.clamp(0.0, 1.0);
+
+    // the last stage's output is `a^4 * u + sigma`, so the feedback loop can be solved for `u` directly
+    let sigma = stages.iter().fold(0.0, |acc, s| acc * a + s / (1.0 + g));
+    // the tiny offset gives self-oscillation something to grow from when the input is silent
+    let u = (drive.max(0.0) * input + 1e-6 - k * sigma) / (1.0 + k * a.powi(4));
+
+    let mut x = u.tanh();
+    let mut taps = [0.0; 4];
+    for (s, tap) in stages.iter_mut().zip(taps.iter_mut()) {
+        let v = (x - *s) * a;
+        *tap = v + *s;
+        *s = *tap + v;
+        x = tap.tanh();
+    }
+
+    if stages.iter().any(|s| !s.is_finite()) {
+        *stages = [0.0; 4];
+    }
+
+    *out = if *two_pole { taps[1] } else { taps[3] };
+
+    Ok(())
+}
+
+impl Default for LadderFilter {
+    fn default() -> Self {
+        Self {
+            stages: [0.0; 4],
+            input: 0.0,
+            cutoff: 1000.0,
+            resonance: 0.0,
+            drive: 1.0,
+            two_pole: false,
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum CombMode {
+    /// Adds a delayed copy of the input, notching out evenly spaced frequencies.
+    #[default]
+    Feedforward,
+    /// Feeds the delayed output back in, ringing at evenly spaced frequencies.
+    Feedback,
+}
+
+/// A comb filter with a fractionally interpolated delay.
+///
+/// The delay is `delay` seconds, or one period of `frequency` when that is above zero, up to the
+/// maximum given at construction. `feedback` scales the delayed signal, and `damping` (0 to 1)
+/// lowpasses it so that high frequencies die away sooner.
+#[processor(allocate = comb_filter_allocate)]
+#[allow(unused)]
+pub fn comb_filter(
+    env: ProcEnv,
+    #[state] mode: &mut CombMode,
+    #[state] max_delay: &mut f32,
+    #[state] line: &mut DelayLine,
+    #[state] damped: &mut f32,
+    #[input] input: &f32,
+    #[input] delay: &f32,
+    #[input] frequency: &f32,
+    #[input] feedback: &f32,
+    #[input] damping: &f32,
+    #[output] out: &mut f32,
+) -> ProcResult<()> {
+    if line.len() < 8 {
+        *out = *input;
+        return Ok(());
+    }
+
+    let delay = if *frequency > 0.0 {
+        frequency.recip()
+    } else {
+        *delay
+    };
+    let delay = (delay * env.sample_rate).clamp(2.0, (line.len() - 3) as f32);
+
+    let delayed = line.read(delay);
+    *damped = delayed + (*damped - delayed) * damping.clamp(0.0, 1.0);
+
+    match mode {
+        CombMode::Feedforward => {
+            *out = input + feedback.clamp(-1.0, 1.0) * *damped;
+            line.write(*input);
+        }
+        CombMode::Feedback => {
+            *out = input + feedback.clamp(-0.999, 0.999) * *damped;
+            line.write(*out);
+        }
+    }
+
+    Ok(())
+}
+
+fn comb_filter_allocate(proc: &mut CombFilter, sample_rate: f32, _block_size: usize) {
+    let len = (proc.max_delay.max(0.0) * sample_rate).ceil() as usize + 4;
+    proc.line = DelayLine::new(len);
+    proc.damped = 0.0;
+}
+
+impl CombFilter {
+    /// Constructs a new [`CombFilter`] that can delay by up to `max_delay` seconds.
+    pub fn new(mode: CombMode, max_delay: f32) -> Self {
+        Self {
+            mode,
+            max_delay,
+            line: DelayLine::default(),
+            damped: 0.0,
+            input: 0.0,
+            delay: 0.01,
+            frequency: 0.0,
+            feedback: 0.5,
+            damping: 0.0,
+        }
+    }
+
+    pub fn feedforward(max_delay: f32) -> Self {
+        Self::new(CombMode::Feedforward, max_delay)
+    }
+
+    pub fn feedback(max_delay: f32) -> Self {
+        Self::new(CombMode::Feedback, max_delay)
+    }
+}
+
+impl Default for CombFilter {
+    fn default() -> Self {
+        Self::feedforward(0.05)
+    }
+}
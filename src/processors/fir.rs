@@ -0,0 +1,302 @@
+use std::f32::consts::PI;
+
+use raug::prelude::*;
+use rustfft::{FftPlanner, num_complex::Complex};
+
+/// The normalized sinc function, `sin(pi * x) / (pi * x)`.
+#[inline]
+pub fn sinc(x: f32) -> f32 {
+    if x == 0.0 {
+        1.0
+    } else {
+        (PI * x).sin() / (PI * x)
+    }
+}
+
+/// A Hann window centered on zero, falling to zero at `x = ±half_width`.
+#[inline]
+pub fn hann(x: f32, half_width: f32) -> f32 {
+    0.5 * (1.0 + (PI * x / half_width).cos())
+}
+
+/// A Hann-windowed sinc kernel, zero outside `-half_width..half_width`.
+#[inline]
+pub fn windowed_sinc(x: f32, half_width: f32) -> f32 {
+    if x.abs() < half_width {
+        sinc(x) * hann(x, half_width)
+    } else {
+        0.0
+    }
+}
+
+/// The zeroth-order modified Bessel function of the first kind.
+fn bessel_i0(x: f32) -> f32 {
+    let mut sum = 1.0;
+    let mut term = 1.0;
+    let half = 0.5 * x;
+    for k in 1..50 {
+        term *= (half / k as f32) * (half / k as f32);
+        sum += term;
+        if term < sum * 1e-9 {
+            break;
+        }
+    }
+    sum
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum FirWindow {
+    Rectangular,
+    Hann,
+    Hamming,
+    #[default]
+    Blackman,
+    /// Higher `beta` trades a wider transition band for more stopband attenuation.
+    Kaiser {
+        beta: f32,
+    },
+}
+
+impl FirWindow {
+    /// A Kaiser window reaching roughly `attenuation` dB of stopband attenuation.
+    pub fn kaiser_for(attenuation: f32) -> Self {
+        let beta = if attenuation > 50.0 {
+            0.1102 * (attenuation - 8.7)
+        } else if attenuation >= 21.0 {
+            0.5842 * (attenuation - 21.0).powf(0.4) + 0.07886 * (attenuation - 21.0)
+        } else {
+            0.0
+        };
+        FirWindow::Kaiser { beta }
+    }
+
+    /// Evaluates the window at tap `n` of `len`.
+    #[inline]
+    pub fn at(self, n: usize, len: usize) -> f32 {
+        if len < 2 {
+            return 1.0;
+        }
+        let t = n as f32 / (len - 1) as f32;
+        match self {
+            FirWindow::Rectangular => 1.0,
+            FirWindow::Hann => {
+                let half_width = 0.5 * (len - 1) as f32;
+                hann(n as f32 - half_width, half_width)
+            }
+            FirWindow::Hamming => 0.54 - 0.46 * (2.0 * PI * t).cos(),
+            FirWindow::Blackman => 0.42 - 0.5 * (2.0 * PI * t).cos() + 0.08 * (4.0 * PI * t).cos(),
+            FirWindow::Kaiser { beta } => {
+                let x = 2.0 * t - 1.0;
+                bessel_i0(beta * (1.0 - x * x).max(0.0).sqrt()) / bessel_i0(beta)
+            }
+        }
+    }
+}
+
+/// The passband of a windowed-sinc FIR design. Frequencies are in Hz.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FirBand {
+    Lowpass(f32),
+    Highpass(f32),
+    Bandpass(f32, f32),
+}
+
+/// Designs a linear-phase windowed-sinc filter.
+///
+/// `taps` is rounded up to an odd number so that highpass and bandpass designs are possible, and the
+/// result is normalized to unity gain in the middle of the passband.
+pub fn design_fir(band: FirBand, taps: usize, window: FirWindow, sample_rate: f32) -> Vec<f32> {
+    let len = taps.max(1) | 1;
+    let center = (len / 2) as f32;
+    let nyquist = 0.5 * sample_rate;
+    let norm = |f: f32| f.clamp(0.0, nyquist) / sample_rate;
+
+    // ideal lowpass impulse response with a cutoff of `fc` cycles per sample
+    let ideal = |fc: f32, n: f32| 2.0 * fc * sinc(2.0 * fc * n);
+
+    let (kernel, reference): (Box<dyn Fn(f32) -> f32>, f32) = match band {
+        FirBand::Lowpass(cutoff) => {
+            let fc = norm(cutoff);
+            (Box::new(move |n| ideal(fc, n)), 0.0)
+        }
+        FirBand::Highpass(cutoff) => {
+            let fc = norm(cutoff);
+            // spectral inversion of the lowpass
+            (
+                Box::new(move |n| if n == 0.0 { 1.0 } else { 0.0 } - ideal(fc, n)),
+                0.5,
+            )
+        }
+        FirBand::Bandpass(low, high) => {
+            let (f1, f2) = (norm(low.min(high)), norm(low.max(high)));
+            (
+                Box::new(move |n| ideal(f2, n) - ideal(f1, n)),
+                0.5 * (f1 + f2),
+            )
+        }
+    };
+
+    let mut coeffs: Vec<f32> = (0..len)
+        .map(|i| kernel(i as f32 - center) * window.at(i, len))
+        .collect();
+
+    // normalize the gain at the reference frequency (in cycles per sample)
+    let (re, im) = coeffs
+        .iter()
+        .enumerate()
+        .fold((0.0, 0.0), |(re, im), (i, h)| {
+            let phase = 2.0 * PI * reference * i as f32;
+            (re + h * phase.cos(), im - h * phase.sin())
+        });
+    let gain = (re * re + im * im).sqrt();
+    if gain > f32::EPSILON {
+        for h in coeffs.iter_mut() {
+            *h /= gain;
+        }
+    }
+
+    coeffs
+}
+
+/// Converts a filter to minimum phase with the same magnitude response, using the real cepstrum.
+///
+/// The result responds without the delay of a linear-phase filter, at the cost of a nonlinear phase.
+pub fn minimum_phase(taps: &[f32]) -> Vec<f32> {
+    if taps.len() < 2 {
+        return taps.to_vec();
+    }
+
+    // plenty of padding keeps cepstral aliasing down
+    let size = (8 * taps.len()).next_power_of_two();
+    let mut planner = FftPlanner::<f32>::new();
+    let fft = planner.plan_fft_forward(size);
+    let ifft = planner.plan_fft_inverse(size);
+
+    let mut buf = vec![Complex::default(); size];
+    for (bin, &h) in buf.iter_mut().zip(taps) {
+        *bin = Complex::new(h, 0.0);
+    }
+    fft.process(&mut buf);
+
+    for bin in buf.iter_mut() {
+        *bin = Complex::new(bin.norm().max(1e-7).ln(), 0.0);
+    }
+    ifft.process(&mut buf);
+
+    // fold the anticausal half of the cepstrum onto the causal half
+    let scale = 1.0 / size as f32;
+    for (i, c) in buf.iter_mut().enumerate() {
+        *c *= match i {
+            0 => scale,
+            i if i < size / 2 => 2.0 * scale,
+            i if i == size / 2 => scale,
+            _ => 0.0,
+        };
+    }
+    fft.process(&mut buf);
+
+    for bin in buf.iter_mut() {
+        *bin = bin.exp();
+    }
+    ifft.process(&mut buf);
+
+    buf[..taps.len()].iter().map(|x| x.re * scale).collect()
+}
+
+/// A finite impulse response filter.
+///
+/// Filters built with [`Fir::design`] are designed for the graph's sample rate when it starts.
+#[processor(allocate = fir_allocate)]
+#[allow(unused)]
+pub fn fir(
+    #[state] design: &mut Option<(FirBand, usize, FirWindow)>,
+    #[state] linear_phase: &mut bool,
+    #[state] taps: &mut Vec<f32>,
+    #[state] history: &mut Vec<f32>,
+    #[state] pos: &mut usize,
+    #[input] input: &f32,
+    #[output] out: &mut f32,
+) -> ProcResult<()> {
+    let len = taps.len();
+    if len == 0 {
+        *out = 0.0;
+        return Ok(());
+    }
+
+    // every sample is written twice so the last `len` of them are always contiguous
+    history[*pos] = *input;
+    history[*pos + len] = *input;
+
+    let recent = &history[*pos + 1..=*pos + len];
+    *out = taps
+        .iter()
+        .zip(recent.iter().rev())
+        .map(|(h, x)| h * x)
+        .sum();
+
+    *pos = (*pos + 1) % len;
+
+    Ok(())
+}
+
+fn fir_allocate(proc: &mut Fir, sample_rate: f32, _block_size: usize) {
+    if let Some((band, taps, window)) = proc.design {
+        proc.taps = design_fir(band, taps, window, sample_rate);
+        if !proc.linear_phase {
+            proc.taps = minimum_phase(&proc.taps);
+        }
+    }
+    proc.history = vec![0.0; 2 * proc.taps.len()];
+    proc.pos = 0;
+}
+
+impl Fir {
+    /// Constructs a new [`Fir`] with the given coefficients.
+    pub fn new(taps: Vec<f32>) -> Self {
+        Self {
+            history: vec![0.0; 2 * taps.len()],
+            taps,
+            ..Default::default()
+        }
+    }
+
+    /// Constructs a windowed-sinc [`Fir`]. If `linear_phase` is false, the design is converted to
+    /// minimum phase.
+    pub fn design(band: FirBand, taps: usize, window: FirWindow, linear_phase: bool) -> Self {
+        Self {
+            design: Some((band, taps, window)),
+            linear_phase,
+            ..Default::default()
+        }
+    }
+
+    pub fn lowpass(cutoff: f32, taps: usize) -> Self {
+        Self::design(FirBand::Lowpass(cutoff), taps, FirWindow::Blackman, true)
+    }
+
+    pub fn highpass(cutoff: f32, taps: usize) -> Self {
+        Self::design(FirBand::Highpass(cutoff), taps, FirWindow::Blackman, true)
+    }
+
+    pub fn bandpass(low: f32, high: f32, taps: usize) -> Self {
+        Self::design(
+            FirBand::Bandpass(low, high),
+            taps,
+            FirWindow::Blackman,
+            true,
+        )
+    }
+}
+
+impl Default for Fir {
+    fn default() -> Self {
+        Self {
+            design: None,
+            linear_phase: true,
+            taps: Vec::new(),
+            history: Vec::new(),
+            pos: 0,
+            input: 0.0,
+        }
+    }
+}
@@ -0,0 +1,216 @@
+use std::f32::consts::PI;
+
+use raug::prelude::*;
+
+use super::Adsr;
+
+pub const MAX_OPERATORS: usize = 6;
+
+/// Describes how the operators of an [`FmVoice`] modulate each other.
+///
+/// Operators are processed from the highest index down to operator 0, so an operator modulated by a
+/// higher-numbered operator sees its output from the same sample, and one modulated by a lower-numbered
+/// operator sees its output from the previous sample.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FmAlgorithm {
+    /// `modulators[i]` is a bitmask of the operators that phase-modulate operator `i`.
+    pub modulators: [u8; MAX_OPERATORS],
+    /// A bitmask of the operators that are mixed into the output.
+    pub carriers: u8,
+}
+
+impl FmAlgorithm {
+    pub const fn new(modulators: [u8; MAX_OPERATORS], carriers: u8) -> Self {
+        Self {
+            modulators,
+            carriers,
+        }
+    }
+
+    /// The eight classic four-operator algorithms (as found on the YM2612), with operator 3 at the top of each stack.
+    pub const FOUR_OP: [Self; 8] = [
+        // 3 > 2 > 1 > 0
+        Self::new([0b0010, 0b0100, 0b1000, 0, 0, 0], 0b0001),
+        // (3 + 2) > 1 > 0
+        Self::new([0b0010, 0b1100, 0, 0, 0, 0], 0b0001),
+        // (3 + (2 > 1)) > 0
+        Self::new([0b1010, 0b0100, 0, 0, 0, 0], 0b0001),
+        // ((3 > 2) + 1) > 0
+        Self::new([0b0110, 0, 0b1000, 0, 0, 0], 0b0001),
+        // 3 > 2, 1 > 0
+        Self::new([0b0010, 0, 0b1000, 0, 0, 0], 0b0101),
+        // 3 > (2, 1, 0)
+        Self::new([0b1000, 0b1000, 0b1000, 0, 0, 0], 0b0111),
+        // 3 > 2, 1, 0
+        Self::new([0, 0, 0b1000, 0, 0, 0], 0b0111),
+        // 3, 2, 1, 0
+        Self::new([0; MAX_OPERATORS], 0b1111),
+    ];
+
+    /// DX7 algorithm 1: `5 > 4 > 3 > 2` and `1 > 0`.
+    pub const DX7_1: Self = Self::new([0b000010, 0, 0b001000, 0b010000, 0b100000, 0], 0b000101);
+
+    /// DX7 algorithm 5: three two-operator pairs, `5 > 4`, `3 > 2` and `1 > 0`.
+    pub const DX7_5: Self = Self::new([0b000010, 0, 0b001000, 0, 0b100000, 0], 0b010101);
+
+    /// DX7 algorithm 32: six parallel carriers.
+    pub const DX7_32: Self = Self::new([0; MAX_OPERATORS], 0b111111);
+}
+
+impl Default for FmAlgorithm {
+    fn default() -> Self {
+        Self::FOUR_OP[0]
+    }
+}
+
+/// Settings for a single sine operator of an [`FmVoice`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FmOperator {
+    /// Frequency ratio relative to the voice frequency.
+    pub ratio: f32,
+    /// Detune in cents.
+    pub detune: f32,
+    pub level: f32,
+    /// Self-modulation amount, applied from the average of the operator's last two outputs.
+    pub feedback: f32,
+    pub attack: f32,
+    pub decay: f32,
+    pub sustain: f32,
+    pub release: f32,
+}
+
+impl FmOperator {
+    pub fn new(ratio: f32, level: f32) -> Self {
+        Self {
+            ratio,
+            level,
+            ..Default::default()
+        }
+    }
+}
+
+impl Default for FmOperator {
+    fn default() -> Self {
+        Self {
+            ratio: 1.0,
+            detune: 0.0,
+            level: 1.0,
+            feedback: 0.0,
+            attack: 0.0,
+            decay: 0.0,
+            sustain: 1.0,
+            release: 0.0,
+        }
+    }
+}
+
+#[derive(Default)]
+struct FmOperatorState {
+    phase: f32,
+    out: [f32; 2],
+    envelope: Adsr,
+}
+
+#[processor(allocate = fm_voice_allocate)]
+pub fn fm_voice(
+    env: ProcEnv,
+    #[state] algorithm: &mut FmAlgorithm,
+    #[state] operators: &mut Vec<FmOperator>,
+    #[state] op_states: &mut Vec<FmOperatorState>,
+    #[input] frequency: &f32,
+    #[input] gate: &bool,
+    #[output] out: &mut f32,
+) -> ProcResult<()> {
+    let mut sum = 0.0;
+    let mut carriers = 0;
+
+    for i in (0..operators.len().min(op_states.len())).rev() {
+        let op = &operators[i];
+
+        let mut modulation = 0.0;
+        for (j, other) in op_states.iter().enumerate() {
+            if algorithm.modulators[i] & (1 << j) != 0 {
+                modulation += other.out[0];
+            }
+        }
+
+        let state = &mut op_states[i];
+        let feedback = op.feedback * 0.5 * (state.out[0] + state.out[1]);
+
+        let mut envelope = 0.0;
+        Adsr::process_sample(
+            env,
+            &mut state.envelope.state,
+            &mut state.envelope.last_gate,
+            &mut state.envelope.value,
+            &mut state.envelope.release_level,
+            gate,
+            &op.attack,
+            &op.decay,
+            &op.sustain,
+            &op.release,
+            &mut envelope,
+        )?;
+
+        let y = op.level * envelope * (2.0 * PI * (state.phase + modulation + feedback)).sin();
+        state.out[1] = state.out[0];
+        state.out[0] = y;
+
+        let op_frequency = frequency * op.ratio * (op.detune / 1200.0).exp2();
+        state.phase += op_frequency / env.sample_rate;
+        state.phase -= state.phase.floor();
+
+        if algorithm.carriers & (1 << i) != 0 {
+            sum += y;
+            carriers += 1;
+        }
+    }
+
+    *out = if carriers > 0 {
+        sum / carriers as f32
+    } else {
+        0.0
+    };
+
+    Ok(())
+}
+
+fn fm_voice_allocate(proc: &mut FmVoice, _sample_rate: f32, _block_size: usize) {
+    proc.operators.truncate(MAX_OPERATORS);
+    proc.op_states
+        .resize_with(proc.operators.len(), Default::default);
+}
+
+impl FmVoice {
+    /// Constructs a new [`FmVoice`]. Operators past [`MAX_OPERATORS`] are ignored.
+    pub fn new(algorithm: FmAlgorithm, mut operators: Vec<FmOperator>) -> Self {
+        operators.truncate(MAX_OPERATORS);
+        let op_states = operators.iter().map(|_| Default::default()).collect();
+        Self {
+            algorithm,
+            operators,
+            op_states,
+            frequency: 440.0,
+            gate: false,
+        }
+    }
+
+    /// Constructs a four-operator voice using one of the [`FmAlgorithm::FOUR_OP`] algorithms.
+    pub fn four_op(algorithm: usize) -> Self {
+        Self::new(
+            FmAlgorithm::FOUR_OP[algorithm % FmAlgorithm::FOUR_OP.len()],
+            vec![FmOperator::default(); 4],
+        )
+    }
+
+    /// Constructs a six-operator voice with the given algorithm.
+    pub fn six_op(algorithm: FmAlgorithm) -> Self {
+        Self::new(algorithm, vec![FmOperator::default(); 6])
+    }
+}
+
+impl Default for FmVoice {
+    fn default() -> Self {
+        Self::four_op(0)
+    }
+}
@@ -0,0 +1,217 @@
+use raug::prelude::*;
+
+use super::BiquadState;
+
+pub const NUM_FORMANTS: usize = 5;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum FormantVoice {
+    #[default]
+    Male,
+    Female,
+    Child,
+}
+
+impl Signal for FormantVoice {}
+
+/// One formant: center frequency (Hz), gain (dB) and bandwidth (Hz).
+type Formant = (f32, f32, f32);
+
+/// Vowels A, E, I, O and U, each with five formants.
+type FormantTable = [[Formant; NUM_FORMANTS]; 5];
+
+const MALE: FormantTable = [
+    [
+        (650.0, 0.0, 80.0),
+        (1080.0, -6.0, 90.0),
+        (2650.0, -7.0, 120.0),
+        (2900.0, -8.0, 130.0),
+        (3250.0, -22.0, 140.0),
+    ],
+    [
+        (400.0, 0.0, 70.0),
+        (1700.0, -14.0, 80.0),
+        (2600.0, -12.0, 100.0),
+        (3200.0, -14.0, 120.0),
+        (3580.0, -20.0, 120.0),
+    ],
+    [
+        (290.0, 0.0, 40.0),
+        (1870.0, -15.0, 90.0),
+        (2800.0, -18.0, 100.0),
+        (3250.0, -20.0, 120.0),
+        (3540.0, -30.0, 120.0),
+    ],
+    [
+        (400.0, 0.0, 40.0),
+        (800.0, -10.0, 80.0),
+        (2600.0, -12.0, 100.0),
+        (2800.0, -12.0, 120.0),
+        (3000.0, -26.0, 120.0),
+    ],
+    [
+        (350.0, 0.0, 40.0),
+        (600.0, -20.0, 60.0),
+        (2700.0, -17.0, 100.0),
+        (2900.0, -14.0, 120.0),
+        (3300.0, -26.0, 120.0),
+    ],
+];
+
+const FEMALE: FormantTable = [
+    [
+        (800.0, 0.0, 80.0),
+        (1150.0, -6.0, 90.0),
+        (2900.0, -32.0, 120.0),
+        (3900.0, -20.0, 130.0),
+        (4950.0, -50.0, 140.0),
+    ],
+    [
+        (350.0, 0.0, 60.0),
+        (2000.0, -20.0, 100.0),
+        (2800.0, -15.0, 120.0),
+        (3600.0, -40.0, 150.0),
+        (4950.0, -56.0, 200.0),
+    ],
+    [
+        (270.0, 0.0, 60.0),
+        (2140.0, -12.0, 90.0),
+        (2950.0, -26.0, 100.0),
+        (3900.0, -26.0, 120.0),
+        (4950.0, -44.0, 120.0),
+    ],
+    [
+        (450.0, 0.0, 70.0),
+        (800.0, -11.0, 80.0),
+        (2830.0, -22.0, 100.0),
+        (3800.0, -22.0, 130.0),
+        (4950.0, -50.0, 135.0),
+    ],
+    [
+        (325.0, 0.0, 50.0),
+        (700.0, -16.0, 60.0),
+        (2700.0, -35.0, 170.0),
+        (3800.0, -40.0, 180.0),
+        (4950.0, -60.0, 200.0),
+    ],
+];
+
+// the first three formants follow Peterson and Barney's measurements of children's vowels,
+// and the upper two are extrapolated
+const CHILD: FormantTable = [
+    [
+        (1030.0, 0.0, 100.0),
+        (1370.0, -6.0, 110.0),
+        (3170.0, -28.0, 150.0),
+        (4400.0, -22.0, 160.0),
+        (5500.0, -50.0, 170.0),
+    ],
+    [
+        (690.0, 0.0, 80.0),
+        (2610.0, -16.0, 120.0),
+        (3570.0, -18.0, 150.0),
+        (4400.0, -38.0, 180.0),
+        (5500.0, -56.0, 240.0),
+    ],
+    [
+        (370.0, 0.0, 70.0),
+        (3200.0, -14.0, 110.0),
+        (3730.0, -24.0, 120.0),
+        (4400.0, -28.0, 150.0),
+        (5500.0, -44.0, 150.0),
+    ],
+    [
+        (680.0, 0.0, 80.0),
+        (1060.0, -10.0, 100.0),
+        (3180.0, -22.0, 120.0),
+        (4400.0, -24.0, 160.0),
+        (5500.0, -50.0, 160.0),
+    ],
+    [
+        (430.0, 0.0, 60.0),
+        (1170.0, -16.0, 80.0),
+        (3260.0, -32.0, 200.0),
+        (4400.0, -40.0, 220.0),
+        (5500.0, -60.0, 240.0),
+    ],
+];
+
+impl FormantVoice {
+    fn table(self) -> &'static FormantTable {
+        match self {
+            FormantVoice::Male => &MALE,
+            FormantVoice::Female => &FEMALE,
+            FormantVoice::Child => &CHILD,
+        }
+    }
+}
+
+/// A vowel filter made of parallel bandpass sections, one per formant.
+///
+/// `vowel` morphs continuously through A (0), E (1), I (2), O (3) and U (4). Feed it a harmonically rich
+/// source, such as a sawtooth, for a talking or singing sound.
+#[processor]
+pub fn formant_filter(
+    env: ProcEnv,
+    #[state] sections: &mut [BiquadState; NUM_FORMANTS],
+    #[state] gains: &mut [f32; NUM_FORMANTS],
+    #[state] last_params: &mut Option<(f32, FormantVoice, f32)>,
+    #[input] input: &f32,
+    #[input] vowel: &f32,
+    #[input] voice: &FormantVoice,
+    #[output] out: &mut f32,
+) -> ProcResult<()> {
+    let params = (*vowel, *voice, env.sample_rate);
+    if *last_params != Some(params) {
+        *last_params = Some(params);
+
+        let table = voice.table();
+        let position = vowel.clamp(0.0, 4.0);
+        let from = &table[position as usize];
+        let to = &table[(position as usize + 1).min(4)];
+        let t = position.fract();
+        let max = 0.45 * env.sample_rate;
+
+        for (((section, gain), a), b) in sections.iter_mut().zip(gains.iter_mut()).zip(from).zip(to)
+        {
+            let frequency = a.0 + (b.0 - a.0) * t;
+            let db = a.1 + (b.1 - a.1) * t;
+            let bandwidth = a.2 + (b.2 - a.2) * t;
+
+            section
+                .bandpass(frequency.min(max), frequency / bandwidth, env.sample_rate)
+                .map_err(ProcessorError::new)?;
+            *gain = 10f32.powf(db / 20.0);
+        }
+    }
+
+    *out = sections
+        .iter_mut()
+        .zip(gains.iter())
+        .map(|(section, gain)| gain * section.process(*input))
+        .sum();
+
+    Ok(())
+}
+
+impl FormantFilter {
+    pub fn new(voice: FormantVoice) -> Self {
+        Self {
+            voice,
+            ..Default::default()
+        }
+    }
+}
+
+impl Default for FormantFilter {
+    fn default() -> Self {
+        Self {
+            sections: [BiquadState::default(); NUM_FORMANTS],
+            gains: [0.0; NUM_FORMANTS],
+            last_params: None,
+            input: 0.0,
+            vowel: 0.0,
+            voice: FormantVoice::Male,
+        }
+    }
+}
@@ -0,0 +1,178 @@
+use std::f32::consts::PI;
+
+use rand::{Rng, SeedableRng, rngs::SmallRng};
+use raug::prelude::*;
+
+use super::SampleStorage;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum GrainWindow {
+    #[default]
+    Hann,
+    Triangle,
+    Gaussian,
+    /// Linear fades over the first and last quarter of the grain.
+    Trapezoid,
+    Rectangle,
+}
+
+impl Signal for GrainWindow {}
+
+impl GrainWindow {
+    /// Evaluates the window at `t`, from 0 at the start of the grain to 1 at its end.
+    #[inline]
+    pub fn at(self, t: f32) -> f32 {
+        match self {
+            GrainWindow::Hann => 0.5 - 0.5 * (2.0 * PI * t).cos(),
+            GrainWindow::Triangle => 1.0 - (2.0 * t - 1.0).abs(),
+            GrainWindow::Gaussian => {
+                let x = (t - 0.5) / 0.15;
+                (-0.5 * x * x).exp()
+            }
+            GrainWindow::Trapezoid => (4.0 * t.min(1.0 - t)).min(1.0),
+            GrainWindow::Rectangle => 1.0,
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+struct Grain {
+    active: bool,
+    position: f32,
+    rate: f32,
+    age: f32,
+    length: f32,
+    gain_l: f32,
+    gain_r: f32,
+}
+
+/// Plays overlapping windowed grains read out of a [`SampleStorage`].
+///
+/// `position` and `position_jitter` are fractions of the sample's length, `grain_size` is in seconds,
+/// `density` is in grains per second, and `pitch` and `pitch_jitter` are in semitones. `spread` pans each
+/// grain randomly by up to that amount.
+#[processor(allocate = granulator_allocate)]
+pub fn granulator(
+    env: ProcEnv,
+    #[state] storage: &mut SampleStorage,
+    #[state] grains: &mut Vec<Grain>,
+    #[state] max_grains: &mut usize,
+    #[state] rng: &mut SmallRng,
+    #[state] until_next: &mut f32,
+    #[input] position: &f32,
+    #[input] position_jitter: &f32,
+    #[input] grain_size: &f32,
+    #[input] density: &f32,
+    #[input] pitch: &f32,
+    #[input] pitch_jitter: &f32,
+    #[input] window: &GrainWindow,
+    #[input] spread: &f32,
+    #[output] out_l: &mut f32,
+    #[output] out_r: &mut f32,
+) -> ProcResult<()> {
+    *out_l = 0.0;
+    *out_r = 0.0;
+
+    if storage.is_empty() {
+        return Ok(());
+    }
+    let len = storage.len() as f32;
+
+    if *density > 0.0 {
+        *until_next -= 1.0;
+    }
+    if *density > 0.0 && *until_next <= 0.0 {
+        *until_next += env.sample_rate / density;
+
+        if let Some(grain) = grains
+            .iter_mut()
+            .take(*max_grains)
+            .find(|grain| !grain.active)
+        {
+            let start = position + position_jitter * rng.random_range(-1.0..=1.0);
+            let semitones = pitch + pitch_jitter * rng.random_range(-1.0..=1.0);
+            let pan = spread.clamp(0.0, 1.0) * rng.random_range(-1.0..=1.0);
+            let angle = (pan + 1.0) * PI / 4.0;
+
+            *grain = Grain {
+                active: true,
+                position: start.rem_euclid(1.0) * len,
+                rate: (semitones / 12.0).exp2(),
+                age: 0.0,
+                length: (grain_size * env.sample_rate).max(1.0),
+                gain_l: angle.cos(),
+                gain_r: angle.sin(),
+            };
+        }
+    }
+
+    for grain in grains.iter_mut().filter(|grain| grain.active) {
+        let y = storage.get_interpolated(grain.position) * window.at(grain.age / grain.length);
+        *out_l += y * grain.gain_l;
+        *out_r += y * grain.gain_r;
+
+        grain.position = (grain.position + grain.rate) % len;
+        grain.age += 1.0;
+        grain.active = grain.age < grain.length;
+    }
+
+    // keep the level roughly steady as grains start to overlap
+    let overlap = (density * grain_size).max(1.0);
+    let norm = overlap.sqrt().recip();
+    *out_l *= norm;
+    *out_r *= norm;
+
+    Ok(())
+}
+
+fn granulator_allocate(proc: &mut Granulator, sample_rate: f32, _block_size: usize) {
+    proc.storage.resample(sample_rate);
+    proc.grains = vec![Grain::default(); proc.max_grains];
+}
+
+impl Granulator {
+    /// Constructs a new [`Granulator`] with a pool of `max_grains` voices.
+    pub fn new(storage: SampleStorage, max_grains: usize) -> Self {
+        Self {
+            storage,
+            max_grains,
+            ..Default::default()
+        }
+    }
+
+    /// Like [`Granulator::new`], but the grain scattering is fully determined by `seed`.
+    pub fn seeded(storage: SampleStorage, max_grains: usize, seed: u64) -> Self {
+        Self {
+            rng: SmallRng::seed_from_u64(seed),
+            ..Self::new(storage, max_grains)
+        }
+    }
+
+    pub fn load(path: &str) -> Result<Self, hound::Error> {
+        let storage = SampleStorage::load(path)?;
+        Ok(Self {
+            storage,
+            ..Default::default()
+        })
+    }
+}
+
+impl Default for Granulator {
+    fn default() -> Self {
+        Self {
+            storage: Default::default(),
+            grains: Vec::new(),
+            max_grains: 64,
+            rng: SmallRng::from_os_rng(),
+            until_next: 0.0,
+            position: 0.0,
+            position_jitter: 0.01,
+            grain_size: 0.1,
+            density: 20.0,
+            pitch: 0.0,
+            pitch_jitter: 0.0,
+            window: GrainWindow::Hann,
+            spread: 0.5,
+        }
+    }
+}
@@ -1,19 +1,45 @@
+pub mod analysis;
+pub mod chaos;
 pub mod control;
+pub mod convolution;
+pub mod design;
 pub mod dynamics;
+pub mod eq;
 pub mod filters;
+pub mod fir;
+pub mod fm;
+pub mod formant;
+pub mod granular;
 pub mod list;
 pub mod math;
 pub mod oscillators;
+pub mod physical;
 pub mod storage;
 pub mod time;
 pub mod util;
+pub mod vocoder;
+pub mod waveshaping;
+pub mod wavetable;
 
+pub use analysis::*;
+pub use chaos::*;
 pub use control::*;
+pub use convolution::*;
+pub use design::*;
 pub use dynamics::*;
+pub use eq::*;
 pub use filters::*;
+pub use fir::*;
+pub use fm::*;
+pub use formant::*;
+pub use granular::*;
 pub use list::*;
 pub use math::*;
 pub use oscillators::*;
+pub use physical::*;
 pub use storage::*;
 pub use time::*;
 pub use util::*;
+pub use vocoder::*;
+pub use waveshaping::*;
+pub use wavetable::*;
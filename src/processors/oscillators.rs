@@ -1,7 +1,10 @@
 use std::f32::consts::PI;
 
+use rand::{Rng, SeedableRng, rngs::SmallRng};
 use raug::prelude::*;
 
+use super::Oversampler;
+
 #[processor(derive(Default))]
 pub fn phase_accumulator(
     #[state] t: &mut u32,
@@ -29,77 +32,822 @@ impl PhaseAccumulator {
     }
 }
 
+#[inline]
+fn poly_blep(t: f32, dt: f32) -> f32 {
+    if t < dt {
+        let t = t / dt;
+        t + t - t * t - 1.0
+    } else if t > 1.0 - dt {
+        let t = (t - 1.0) / dt;
+        t * t + t + t + 1.0
+    } else {
+        0.0
+    }
+}
+
+#[inline]
+fn poly_blamp(t: f32, dt: f32) -> f32 {
+    if t < dt {
+        let t = t / dt - 1.0;
+        -t * t * t / 3.0
+    } else if t > 1.0 - dt {
+        let t = (t - 1.0) / dt + 1.0;
+        t * t * t / 3.0
+    } else {
+        0.0
+    }
+}
+
+#[inline]
+fn bl_saw(phase: f32, dt: f32) -> f32 {
+    2.0 * phase - 1.0 - poly_blep(phase, dt)
+}
+
+#[inline]
+fn bl_pulse(phase: f32, dt: f32, pulse_width: f32) -> f32 {
+    let naive = if phase < pulse_width { 1.0 } else { -1.0 };
+    let falling = (phase - pulse_width + 1.0).fract();
+    naive + poly_blep(phase, dt) - poly_blep(falling, dt)
+}
+
+#[inline]
+fn bl_triangle(phase: f32, dt: f32) -> f32 {
+    let naive = 1.0 - 2.0 * (2.0 * phase - 1.0).abs();
+    let peak = (phase + 0.5).fract();
+    naive + 4.0 * dt * (poly_blamp(phase, dt) - poly_blamp(peak, dt))
+}
+
+/// Phase state shared by the oscillators in this module.
+///
+/// Handles phase modulation (in radians), through-zero linear FM (in Hz) and hard sync. A sync trigger is
+/// treated as landing halfway to the next sample, and the resulting discontinuity is smoothed with a
+/// PolyBLEP residual split across the current sample and the next one, so the waveform only falls one
+/// sample behind when a sync actually happens.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct OscillatorCore {
+    pub phase: f32,
+    pending: f32,
+}
+
+impl OscillatorCore {
+    /// Advances the oscillator by one sample and returns its output.
+    ///
+    /// `waveform` is called with a phase in `0..1` and the absolute phase increment per sample, which
+    /// band-limited waveforms can use to size their corrections. With an increment of zero it must return
+    /// the naive, uncorrected waveform, which is what sync steps are measured on.
+    #[inline]
+    pub fn next(
+        &mut self,
+        frequency: f32,
+        phase_mod: f32,
+        fm: f32,
+        sync: bool,
+        sample_rate: f32,
+        waveform: impl Fn(f32, f32) -> f32,
+    ) -> f32 {
+        let dt = (frequency + fm) / sample_rate;
+        let bl_dt = dt.abs().min(0.5);
+        let offset = phase_mod / (2.0 * PI);
+        let read = |phase: f32, dt: f32| {
+            let phase = phase + offset;
+            waveform(phase - phase.floor(), dt)
+        };
+
+        let mut out = read(self.phase, bl_dt) + self.pending;
+        self.pending = 0.0;
+
+        if sync {
+            // the step between the new and old naive waveforms at the next sample
+            let jump = read(0.5 * dt, 0.0) - read(self.phase + dt, 0.0);
+            out += 0.125 * jump;
+            // the restarted phase didn't wrap, so take back the wrap correction the waveform will apply
+            // (along with any other edge within a sample of the restart)
+            let spurious = read(0.5 * dt, bl_dt) - read(0.5 * dt, 0.0);
+            self.pending = -0.125 * jump - spurious;
+            self.phase = 0.5 * dt;
+        } else {
+            self.phase += dt;
+        }
+        self.phase -= self.phase.floor();
+
+        out
+    }
+}
+
+/// `reset` restarts the phase at zero without a click, the same way a `sync` trigger does.
 #[processor(derive(Default))]
 pub fn sine_oscillator(
     env: ProcEnv,
-    #[state] t: &mut f32,
+    #[state] osc: &mut OscillatorCore,
     #[input] frequency: &f32,
     #[input] phase: &f32,
     #[input] reset: &bool,
+    #[input] sync: &bool,
+    #[input] fm: &f32,
     #[output] out: &mut f32,
 ) -> ProcResult<()> {
-    if *reset {
-        *t = 0.0;
-        *out = 0.0;
+    *out = osc.next(
+        *frequency,
+        *phase,
+        *fm,
+        *sync || *reset,
+        env.sample_rate,
+        |p, _| (2.0 * PI * p).sin(),
+    );
+
+    Ok(())
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum NoiseColor {
+    #[default]
+    White,
+    Pink,
+    Brown,
+    Blue,
+    Velvet,
+}
+
+impl Signal for NoiseColor {}
+
+#[derive(Clone)]
+struct NoiseState {
+    rng: SmallRng,
+    pink: [f32; 7],
+    last_pink: f32,
+    brown: f32,
+    velvet_time: f32,
+    velvet_pos: f32,
+    velvet_fired: bool,
+}
+
+impl NoiseState {
+    fn new(rng: SmallRng) -> Self {
+        Self {
+            rng,
+            pink: [0.0; 7],
+            last_pink: 0.0,
+            brown: 0.0,
+            velvet_time: f32::INFINITY,
+            velvet_pos: 0.0,
+            velvet_fired: false,
+        }
+    }
+
+    #[inline]
+    fn white(&mut self) -> f32 {
+        self.rng.random_range(-1.0..1.0)
+    }
+
+    /// Paul Kellet's refined pink noise filter.
+    #[inline]
+    fn pink(&mut self) -> f32 {
+        let white = self.white();
+        let b = &mut self.pink;
+        b[0] = 0.99886 * b[0] + white * 0.0555179;
+        b[1] = 0.99332 * b[1] + white * 0.0750759;
+        b[2] = 0.96900 * b[2] + white * 0.153852;
+        b[3] = 0.86650 * b[3] + white * 0.3104856;
+        b[4] = 0.55000 * b[4] + white * 0.5329522;
+        b[5] = -0.7616 * b[5] - white * 0.0168980;
+        let pink = b.iter().sum::<f32>() + white * 0.5362;
+        b[6] = white * 0.115926;
+        pink * 0.11
+    }
+}
+
+impl Default for NoiseState {
+    fn default() -> Self {
+        Self::new(SmallRng::from_os_rng())
+    }
+}
+
+#[processor]
+pub fn noise_oscillator(
+    env: ProcEnv,
+    #[state] state: &mut NoiseState,
+    #[input] color: &NoiseColor,
+    #[input] density: &f32,
+    #[output] out: &mut f32,
+) -> ProcResult<()> {
+    *out = match color {
+        NoiseColor::White => state.white(),
+        NoiseColor::Pink => state.pink(),
+        NoiseColor::Brown => {
+            let white = state.white();
+            state.brown = (state.brown + 0.02 * white) / 1.02;
+            state.brown * 3.5
+        }
+        NoiseColor::Blue => {
+            let pink = state.pink();
+            let blue = pink - state.last_pink;
+            state.last_pink = pink;
+            blue * 2.5
+        }
+        NoiseColor::Velvet => {
+            // one impulse of random sign at a random position within each period
+            let period = (env.sample_rate / density.max(1.0)).max(1.0);
+            if state.velvet_time >= period {
+                state.velvet_time = 0.0;
+                state.velvet_pos = state.rng.random_range(0.0..period).floor();
+                state.velvet_fired = false;
+            }
+
+            let impulse = if !state.velvet_fired && state.velvet_time >= state.velvet_pos {
+                state.velvet_fired = true;
+                if state.rng.random::<bool>() {
+                    1.0
+                } else {
+                    -1.0
+                }
+            } else {
+                0.0
+            };
+
+            state.velvet_time += 1.0;
+            impulse
+        }
+    };
+
+    Ok(())
+}
+
+impl NoiseOscillator {
+    /// Constructs a new [`NoiseOscillator`] whose output is fully determined by `seed`.
+    pub fn new(color: NoiseColor, seed: u64) -> Self {
+        Self {
+            state: NoiseState::new(SmallRng::seed_from_u64(seed)),
+            color,
+            ..Default::default()
+        }
+    }
+}
+
+impl Default for NoiseOscillator {
+    fn default() -> Self {
+        Self {
+            state: NoiseState::default(),
+            color: NoiseColor::White,
+            density: 2000.0,
+        }
+    }
+}
+
+#[processor]
+pub fn bl_saw_oscillator(
+    env: ProcEnv,
+    #[state] osc: &mut OscillatorCore,
+    #[input] frequency: &f32,
+    #[input] phase: &f32,
+    #[input] sync: &bool,
+    #[input] fm: &f32,
+    #[output] out: &mut f32,
+) -> ProcResult<()> {
+    *out = osc.next(*frequency, *phase, *fm, *sync, env.sample_rate, bl_saw);
+
+    Ok(())
+}
+
+impl Default for BlSawOscillator {
+    fn default() -> Self {
+        BlSawOscillator {
+            osc: OscillatorCore::default(),
+            frequency: 440.0,
+            phase: 0.0,
+            sync: false,
+            fm: 0.0,
+        }
+    }
+}
+
+#[processor]
+pub fn bl_pulse_oscillator(
+    env: ProcEnv,
+    #[state] osc: &mut OscillatorCore,
+    #[input] frequency: &f32,
+    #[input] pulse_width: &f32,
+    #[input] phase: &f32,
+    #[input] reset: &bool,
+    #[input] sync: &bool,
+    #[input] fm: &f32,
+    #[output] out: &mut f32,
+) -> ProcResult<()> {
+    *out = osc.next(
+        *frequency,
+        *phase,
+        *fm,
+        *sync || *reset,
+        env.sample_rate,
+        |p, dt| bl_pulse(p, dt, pulse_width.clamp(dt, 1.0 - dt)),
+    );
+
+    Ok(())
+}
+
+impl Default for BlPulseOscillator {
+    fn default() -> Self {
+        BlPulseOscillator {
+            osc: OscillatorCore::default(),
+            frequency: 440.0,
+            pulse_width: 0.5,
+            phase: 0.0,
+            reset: false,
+            sync: false,
+            fm: 0.0,
+        }
+    }
+}
+
+#[processor]
+pub fn bl_square_oscillator(
+    env: ProcEnv,
+    #[state] osc: &mut OscillatorCore,
+    #[input] frequency: &f32,
+    #[input] phase: &f32,
+    #[input] reset: &bool,
+    #[input] sync: &bool,
+    #[input] fm: &f32,
+    #[output] out: &mut f32,
+) -> ProcResult<()> {
+    *out = osc.next(
+        *frequency,
+        *phase,
+        *fm,
+        *sync || *reset,
+        env.sample_rate,
+        |p, dt| bl_pulse(p, dt, 0.5),
+    );
+
+    Ok(())
+}
+
+impl Default for BlSquareOscillator {
+    fn default() -> Self {
+        BlSquareOscillator {
+            osc: OscillatorCore::default(),
+            frequency: 440.0,
+            phase: 0.0,
+            reset: false,
+            sync: false,
+            fm: 0.0,
+        }
+    }
+}
+
+#[processor]
+pub fn bl_triangle_oscillator(
+    env: ProcEnv,
+    #[state] osc: &mut OscillatorCore,
+    #[input] frequency: &f32,
+    #[input] phase: &f32,
+    #[input] reset: &bool,
+    #[input] sync: &bool,
+    #[input] fm: &f32,
+    #[output] out: &mut f32,
+) -> ProcResult<()> {
+    *out = osc.next(
+        *frequency,
+        *phase,
+        *fm,
+        *sync || *reset,
+        env.sample_rate,
+        bl_triangle,
+    );
+
+    Ok(())
+}
+
+impl Default for BlTriangleOscillator {
+    fn default() -> Self {
+        BlTriangleOscillator {
+            osc: OscillatorCore::default(),
+            frequency: 440.0,
+            phase: 0.0,
+            reset: false,
+            sync: false,
+            fm: 0.0,
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum LfoShape {
+    #[default]
+    Sine,
+    Triangle,
+    SawUp,
+    SawDown,
+    Square,
+    SampleAndHold,
+    SmoothRandom,
+}
+
+impl Signal for LfoShape {}
+
+#[derive(Clone)]
+struct LfoState {
+    phase: f32,
+    /// Whole cycles completed, and the last cycle (including the phase offset) a random value was drawn for.
+    cycle: i64,
+    drawn: i64,
+    rng: SmallRng,
+    /// The previous and current random values, for the random shapes.
+    held: [f32; 2],
+    last_sync: bool,
+    /// Measured time between clock ticks, in samples (zero until two ticks have been seen).
+    tick_period: f32,
+    since_tick: f32,
+    tick_phase: f32,
+    clocked: bool,
+}
+
+impl LfoState {
+    fn new(mut rng: SmallRng) -> Self {
+        let held = [rng.random_range(-1.0..1.0), rng.random_range(-1.0..1.0)];
+        Self {
+            phase: 0.0,
+            cycle: 0,
+            drawn: 0,
+            rng,
+            held,
+            last_sync: false,
+            tick_period: 0.0,
+            since_tick: 0.0,
+            tick_phase: 0.0,
+            clocked: false,
+        }
+    }
+
+    /// Moves the phase by `delta` cycles, counting every wrap.
+    #[inline]
+    fn advance(&mut self, delta: f32) {
+        let next = self.phase + delta;
+        self.cycle += next.floor() as i64;
+        self.phase = next - next.floor();
+    }
+}
+
+impl Default for LfoState {
+    fn default() -> Self {
+        Self::new(SmallRng::from_os_rng())
+    }
+}
+
+/// A low-frequency oscillator for modulation.
+///
+/// `frequency` is in Hz when free-running, in cycles per beat when `bpm` is positive, and in cycles per
+/// tick once the `sync` input has received clock ticks (e.g. from a [`Metro`](super::Metro)). When
+/// clocked, the phase is re-aligned on every tick so the LFO stays locked to the clock.
+#[processor]
+pub fn lfo(
+    env: ProcEnv,
+    #[state] state: &mut LfoState,
+    #[input] shape: &LfoShape,
+    #[input] frequency: &f32,
+    #[input] bpm: &f32,
+    #[input] sync: &bool,
+    #[input] phase: &f32,
+    #[input] unipolar: &bool,
+    #[output] out: &mut f32,
+) -> ProcResult<()> {
+    if *sync && !state.last_sync {
+        if state.clocked {
+            state.tick_period = state.since_tick;
+            state.tick_phase += frequency;
+            state.tick_phase -= state.tick_phase.floor();
+        } else {
+            state.clocked = true;
+            state.tick_phase = 0.0;
+        }
+        state.since_tick = 0.0;
+        // realign by the shortest way around, so a tick landing just after a wrap doesn't count it twice
+        let error = state.tick_phase - state.phase;
+        state.advance(error - (error + 0.5).floor());
+    }
+    state.last_sync = *sync;
+
+    let dt = if state.clocked {
+        if state.tick_period > 0.0 {
+            frequency / state.tick_period
+        } else {
+            0.0
+        }
+    } else if *bpm > 0.0 {
+        frequency * bpm / 60.0 / env.sample_rate
     } else {
-        *t += frequency / env.sample_rate * 2.0 * std::f32::consts::PI;
-        *out = (*t + phase).sin();
+        frequency / env.sample_rate
+    };
+
+    let p = state.phase + phase;
+    let cycle = state.cycle + p.floor() as i64;
+    let p = p - p.floor();
+
+    // draw a new random value whenever the offset phase wraps around in the direction of travel
+    if cycle > state.drawn || (dt < 0.0 && cycle < state.drawn) {
+        state.drawn = cycle;
+        state.held[0] = state.held[1];
+        state.held[1] = state.rng.random_range(-1.0..1.0);
     }
-    *t %= 2.0 * std::f32::consts::PI;
+
+    let value = match shape {
+        LfoShape::Sine => (2.0 * PI * p).sin(),
+        LfoShape::Triangle => 1.0 - 4.0 * ((p + 0.25).fract() - 0.5).abs(),
+        LfoShape::SawUp => 2.0 * p - 1.0,
+        LfoShape::SawDown => 1.0 - 2.0 * p,
+        LfoShape::Square => {
+            if p < 0.5 {
+                1.0
+            } else {
+                -1.0
+            }
+        }
+        LfoShape::SampleAndHold => state.held[1],
+        LfoShape::SmoothRandom => {
+            let t = 0.5 - 0.5 * (PI * p).cos();
+            state.held[0] + (state.held[1] - state.held[0]) * t
+        }
+    };
+
+    *out = if *unipolar {
+        0.5 * (value + 1.0)
+    } else {
+        value
+    };
+
+    state.advance(dt);
+    state.since_tick += 1.0;
 
     Ok(())
 }
 
-#[processor(derive(Default))]
-pub fn noise_oscillator(#[output] out: &mut f32) -> ProcResult<()> {
-    *out = rand::random::<f32>();
+impl Lfo {
+    pub fn new(shape: LfoShape, frequency: f32) -> Self {
+        Self {
+            shape,
+            frequency,
+            ..Default::default()
+        }
+    }
+
+    /// Constructs a new [`Lfo`] whose random shapes are fully determined by `seed`.
+    pub fn seeded(shape: LfoShape, frequency: f32, seed: u64) -> Self {
+        Self {
+            state: LfoState::new(SmallRng::seed_from_u64(seed)),
+            ..Self::new(shape, frequency)
+        }
+    }
+}
+
+impl Default for Lfo {
+    fn default() -> Self {
+        Self {
+            state: LfoState::default(),
+            shape: LfoShape::Sine,
+            frequency: 1.0,
+            bpm: 0.0,
+            sync: false,
+            phase: 0.0,
+            unipolar: false,
+        }
+    }
+}
+
+pub const MAX_UNISON_VOICES: usize = 16;
+
+/// A stack of detuned band-limited saws, spread across the stereo field.
+///
+/// Voice 0 stays at the center, untouched by `detune` and `spread`; the others fan out symmetrically
+/// around it. `detune` runs from 0 to 1 through a cubic curve (reaching two semitones), and `mix`
+/// crossfades from the center voice alone to all voices at equal level.
+#[processor]
+pub fn supersaw(
+    env: ProcEnv,
+    #[state] oscs: &mut [OscillatorCore; MAX_UNISON_VOICES],
+    #[input] frequency: &f32,
+    #[input] voices: &f32,
+    #[input] detune: &f32,
+    #[input] spread: &f32,
+    #[input] mix: &f32,
+    #[output] out_l: &mut f32,
+    #[output] out_r: &mut f32,
+) -> ProcResult<()> {
+    let voices = (voices.round() as usize).clamp(1, MAX_UNISON_VOICES);
+    let detune = detune.clamp(0.0, 1.0);
+    let semitones = 2.0 * (0.25 * detune + 0.75 * detune * detune * detune);
+    let spread = spread.clamp(0.0, 1.0);
+    let mix = mix.clamp(0.0, 1.0);
+
+    let center_gain = 1.0 - 0.5 * mix;
+    let side_gain = mix;
+    let side_pairs = (voices / 2).max(1) as f32;
+
+    *out_l = 0.0;
+    *out_r = 0.0;
+
+    for (i, osc) in oscs.iter_mut().take(voices).enumerate() {
+        let (offset, gain) = if i == 0 {
+            (0.0, center_gain)
+        } else {
+            let sign = if i % 2 == 1 { 1.0 } else { -1.0 };
+            (sign * i.div_ceil(2) as f32 / side_pairs, side_gain)
+        };
+
+        let ratio = (offset * semitones / 12.0).exp2();
+        let y = gain * osc.next(frequency * ratio, 0.0, 0.0, false, env.sample_rate, bl_saw);
+
+        // equal-power panning
+        let angle = (offset * spread + 1.0) * PI / 4.0;
+        *out_l += y * angle.cos();
+        *out_r += y * angle.sin();
+    }
+
+    // keep the overall level steady as voices are added
+    let power = center_gain * center_gain + (voices - 1) as f32 * side_gain * side_gain;
+    let norm = power.sqrt().max(1.0);
+    *out_l /= norm;
+    *out_r /= norm;
+
     Ok(())
 }
 
+impl Supersaw {
+    pub fn new(voices: usize) -> Self {
+        Self::seeded(voices, rand::random())
+    }
+
+    /// Constructs a new [`Supersaw`] whose initial voice phases are fully determined by `seed`.
+    pub fn seeded(voices: usize, seed: u64) -> Self {
+        let mut rng = SmallRng::seed_from_u64(seed);
+        let mut oscs = [OscillatorCore::default(); MAX_UNISON_VOICES];
+        for osc in oscs.iter_mut() {
+            osc.phase = rng.random();
+        }
+        Self {
+            oscs,
+            frequency: 440.0,
+            voices: voices.clamp(1, MAX_UNISON_VOICES) as f32,
+            detune: 0.25,
+            spread: 0.5,
+            mix: 0.5,
+        }
+    }
+}
+
+impl Default for Supersaw {
+    fn default() -> Self {
+        Self::new(7)
+    }
+}
+
+pub const MAX_PARTIALS: usize = 64;
+
+/// Sums up to [`MAX_PARTIALS`] sine partials, with amplitudes taken from the `amplitudes` list.
+///
+/// Partial `n` (counting from 1) sits at `n * frequency * sqrt(1 + inharmonicity * n^2)`, like a stiff
+/// string, and its amplitude is tilted by `tilt` dB per octave. Partials at or above Nyquist are skipped.
 #[processor]
-pub fn bl_saw_oscillator(
+pub fn additive_oscillator(
     env: ProcEnv,
-    #[state] p: &mut f32,
-    #[state] dp: &mut f32,
-    #[state] saw: &mut f32,
+    #[state] phases: &mut [f32; MAX_PARTIALS],
     #[input] frequency: &f32,
+    #[input] amplitudes: &List<f32>,
+    #[input] inharmonicity: &f32,
+    #[input] tilt: &f32,
     #[output] out: &mut f32,
 ) -> ProcResult<()> {
-    if *frequency <= 0.0 {
-        *out = 0.0;
-        return Ok(());
+    let nyquist = 0.5 * env.sample_rate;
+    let inharmonicity = inharmonicity.max(0.0);
+    let amplitudes: &[f32] = amplitudes.as_ref();
+
+    *out = 0.0;
+
+    for (i, (phase, amp)) in phases.iter_mut().zip(amplitudes).enumerate() {
+        let n = (i + 1) as f32;
+        let partial_freq = n * frequency * (1.0 + inharmonicity * n * n).sqrt();
+        if partial_freq.abs() >= nyquist {
+            // partials only get higher from here
+            break;
+        }
+
+        let gain = amp * 10.0f32.powf(tilt * n.log2() / 20.0);
+        *out += gain * (2.0 * PI * *phase).sin();
+
+        *phase += partial_freq / env.sample_rate;
+        *phase -= phase.floor();
     }
 
-    let pmax = 0.5 * env.sample_rate / frequency;
-    let dc = -0.498 / pmax;
+    Ok(())
+}
 
-    *p += *dp;
-    if *p < 0.0 {
-        *p = -*p;
-        *dp = -*dp;
-    } else if *p > pmax {
-        *p = 2.0 * pmax - *p;
-        *dp = -*dp;
+impl AdditiveOscillator {
+    pub fn new(amplitudes: &[f32]) -> Self {
+        Self {
+            amplitudes: List::from_slice(amplitudes),
+            ..Default::default()
+        }
     }
+}
 
-    let mut x = PI * *p;
-    if x < 0.00001 {
-        x = 0.00001;
+impl Default for AdditiveOscillator {
+    fn default() -> Self {
+        Self {
+            phases: [0.0; MAX_PARTIALS],
+            frequency: 440.0,
+            amplitudes: List::from_slice(&[1.0]),
+            inharmonicity: 0.0,
+            tilt: 0.0,
+        }
     }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum PdShape {
+    #[default]
+    Saw,
+    Pulse,
+    /// A sine burst at `1 + 15 * distortion` times the fundamental, under a falling saw window.
+    Resonant,
+}
 
-    *saw = 0.995 * *saw + dc + x.sin() / x;
-    *out = *saw;
+impl Signal for PdShape {}
+
+#[inline]
+fn pd_waveform(shape: PdShape, phase: f32, distortion: f32) -> f32 {
+    let distortion = distortion.clamp(0.0, 1.0);
+    // breakpoint of the phase transfer curve; a knee at 0.5 leaves the cosine undistorted
+    let knee = 0.5 - 0.49 * distortion;
+
+    let warped = match shape {
+        PdShape::Saw => {
+            if phase < knee {
+                0.5 * phase / knee
+            } else {
+                0.5 + 0.5 * (phase - knee) / (1.0 - knee)
+            }
+        }
+        PdShape::Pulse => {
+            let half = if phase < 0.5 { 0.0 } else { 0.5 };
+            half + 0.5 * ((phase - half) / knee).min(1.0)
+        }
+        PdShape::Resonant => {
+            let ratio = 1.0 + 15.0 * distortion;
+            return (1.0 - phase) * (2.0 * PI * phase * ratio).sin();
+        }
+    };
+
+    (2.0 * PI * warped).cos()
+}
+
+/// A Casio CZ-style phase distortion oscillator, with optional internal oversampling.
+#[processor(allocate = pd_oscillator_allocate)]
+pub fn pd_oscillator(
+    env: ProcEnv,
+    #[state] osc: &mut OscillatorCore,
+    #[state] oversampler: &mut Oversampler,
+    #[input] frequency: &f32,
+    #[input] shape: &PdShape,
+    #[input] distortion: &f32,
+    #[input] phase: &f32,
+    #[input] sync: &bool,
+    #[input] fm: &f32,
+    #[output] out: &mut f32,
+) -> ProcResult<()> {
+    let rate = env.sample_rate * oversampler.factor() as f32;
+
+    *out = oversampler.process(|k| {
+        osc.next(*frequency, *phase, *fm, *sync && k == 0, rate, |p, _| {
+            pd_waveform(*shape, p, *distortion)
+        })
+    });
 
     Ok(())
 }
 
-impl Default for BlSawOscillator {
+fn pd_oscillator_allocate(proc: &mut PdOscillator, sample_rate: f32, _block_size: usize) {
+    proc.oversampler.allocate(sample_rate);
+}
+
+impl PdOscillator {
+    pub fn new(shape: PdShape, oversample: usize) -> Self {
+        Self {
+            shape,
+            oversampler: Oversampler::new(oversample),
+            ..Default::default()
+        }
+    }
+}
+
+impl Default for PdOscillator {
     fn default() -> Self {
-        BlSawOscillator {
-            p: 0.0,
-            dp: 1.0,
-            saw: 0.0,
+        Self {
+            osc: OscillatorCore::default(),
+            oversampler: Oversampler::new(2),
             frequency: 440.0,
+            shape: PdShape::Saw,
+            distortion: 0.5,
+            phase: 0.0,
+            sync: false,
+            fm: 0.0,
         }
     }
 }
@@ -0,0 +1,248 @@
+use std::f32::consts::PI;
+
+use rand::{Rng, SeedableRng, rngs::SmallRng};
+use raug::prelude::*;
+
+use super::{BiquadState, DelayLine};
+
+/// The lowest frequency a [`PluckedString`] can be tuned to, which sizes its delay line.
+const MIN_STRING_FREQUENCY: f32 = 20.0;
+
+/// A Karplus-Strong plucked string.
+///
+/// `damping` sets how fast the string loses energy, `brightness` controls both the excitation and the
+/// high-frequency loss in the loop, and `pick_position` (0 to 1 along the string) notches out the
+/// harmonics that have a node at the pick point.
+#[processor(allocate = plucked_string_allocate)]
+pub fn plucked_string(
+    env: ProcEnv,
+    #[state] line: &mut DelayLine,
+    #[state] burst: &mut Vec<f32>,
+    #[state] rng: &mut SmallRng,
+    #[state] last_trig: &mut bool,
+    #[state] prev: &mut f32,
+    #[input] trig: &bool,
+    #[input] frequency: &f32,
+    #[input] damping: &f32,
+    #[input] brightness: &f32,
+    #[input] pick_position: &f32,
+    #[output] out: &mut f32,
+) -> ProcResult<()> {
+    let len = line.len();
+    if len < 8 {
+        *out = 0.0;
+        return Ok(());
+    }
+
+    let brightness = brightness.clamp(0.0, 1.0);
+    // the loop filter is a two-tap average whose group delay is `smoothing` samples
+    let smoothing = 0.5 * (1.0 - brightness);
+    let period = env.sample_rate / frequency.max(MIN_STRING_FREQUENCY);
+    let delay = (period - smoothing).clamp(3.0, (len - 3) as f32);
+
+    if *trig && !*last_trig {
+        let n = (period.ceil() as usize).min(burst.len()).min(len - 1);
+
+        // lowpassed noise burst, one period long
+        let coeff = 0.1 + 0.9 * brightness;
+        let mut lp = 0.0;
+        for x in burst[..n].iter_mut() {
+            lp += coeff * (rng.random_range(-1.0..1.0) - lp);
+            *x = lp;
+        }
+
+        // comb out the harmonics with a node at the pick position
+        let pick = (pick_position.clamp(0.0, 1.0) * n as f32).round() as usize;
+        if pick > 0 && pick < n {
+            for i in (pick..n).rev() {
+                burst[i] -= burst[i - pick];
+            }
+        }
+
+        // write the burst just behind the write head so it plays out over the next period
+        for (i, x) in burst[..n].iter().enumerate() {
+            line.overwrite(n - i, *x);
+        }
+        *prev = 0.0;
+    }
+    *last_trig = *trig;
+
+    let delayed = line.read(delay);
+
+    let filtered = (1.0 - smoothing) * delayed + smoothing * *prev;
+    *prev = delayed;
+
+    let gain = 1.0 - 0.02 * damping.clamp(0.0, 1.0);
+    line.write(filtered * gain);
+
+    *out = delayed;
+
+    Ok(())
+}
+
+fn plucked_string_allocate(proc: &mut PluckedString, sample_rate: f32, _block_size: usize) {
+    let len = (sample_rate / MIN_STRING_FREQUENCY).ceil() as usize + 4;
+    proc.line = DelayLine::new(len);
+    proc.burst = vec![0.0; len];
+}
+
+impl PluckedString {
+    /// Constructs a new [`PluckedString`] whose excitation noise is fully determined by `seed`.
+    pub fn seeded(seed: u64) -> Self {
+        Self {
+            rng: SmallRng::seed_from_u64(seed),
+            ..Default::default()
+        }
+    }
+}
+
+impl Default for PluckedString {
+    fn default() -> Self {
+        Self {
+            line: DelayLine::default(),
+            burst: Vec::new(),
+            rng: SmallRng::from_os_rng(),
+            last_trig: false,
+            prev: 0.0,
+            trig: false,
+            frequency: 220.0,
+            damping: 0.1,
+            brightness: 0.5,
+            pick_position: 0.2,
+        }
+    }
+}
+
+/// A single resonant mode of a [`ModalResonator`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Mode {
+    /// Frequency relative to the resonator's fundamental.
+    pub ratio: f32,
+    /// Time to decay by 60 dB, in seconds.
+    pub decay: f32,
+    pub gain: f32,
+}
+
+impl Mode {
+    pub const fn new(ratio: f32, decay: f32, gain: f32) -> Self {
+        Self { ratio, decay, gain }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ModalPreset {
+    /// A free-free uniform bar, like a marimba or glockenspiel key.
+    Bar,
+    /// A church bell, with its hum, prime, tierce, quint and nominal partials.
+    Bell,
+    /// A simply-supported square plate.
+    Plate,
+    /// An ideal circular membrane.
+    Membrane,
+}
+
+impl ModalPreset {
+    pub fn modes(self) -> Vec<Mode> {
+        let (ratios, decay): (&[f32], f32) = match self {
+            ModalPreset::Bar => (&[1.0, 2.756, 5.404, 8.933, 13.344, 18.64], 1.5),
+            ModalPreset::Bell => (
+                &[
+                    0.5, 1.0, 1.183, 1.506, 2.0, 2.514, 2.662, 3.011, 4.166, 5.433,
+                ],
+                4.0,
+            ),
+            ModalPreset::Plate => (&[1.0, 2.5, 4.0, 5.0, 6.5, 8.5, 9.0, 10.0, 12.5, 13.0], 2.0),
+            ModalPreset::Membrane => (
+                &[
+                    1.0, 1.594, 2.136, 2.296, 2.653, 2.918, 3.156, 3.501, 3.6, 3.652, 4.06, 4.154,
+                ],
+                0.5,
+            ),
+        };
+
+        // higher modes ring shorter and quieter
+        ratios
+            .iter()
+            .map(|&ratio| Mode::new(ratio, decay / ratio.sqrt(), 1.0 / ratio))
+            .collect()
+    }
+}
+
+/// A bank of tuned resonant bandpass filters running in parallel.
+///
+/// The bank is excited by the `input` signal and/or by an impulse on each `trig`. Mode frequencies are
+/// given as ratios of `frequency`, and `decay` scales every mode's decay time.
+#[processor(allocate = modal_resonator_allocate)]
+pub fn modal_resonator(
+    env: ProcEnv,
+    #[state] modes: &mut Vec<Mode>,
+    #[state] filters: &mut Vec<BiquadState>,
+    #[state] last_params: &mut [f32; 2],
+    #[state] last_trig: &mut bool,
+    #[input] input: &f32,
+    #[input] trig: &bool,
+    #[input] frequency: &f32,
+    #[input] decay: &f32,
+    #[output] out: &mut f32,
+) -> ProcResult<()> {
+    let nyquist = 0.5 * env.sample_rate;
+
+    if *last_params != [*frequency, *decay] {
+        *last_params = [*frequency, *decay];
+        for (mode, filter) in modes.iter().zip(filters.iter_mut()) {
+            let cutoff = frequency * mode.ratio;
+            let t60 = (mode.decay * decay).max(0.001);
+            // a bandwidth of ln(1000) / (pi * t60) Hz decays by 60 dB in t60 seconds
+            let q = PI * cutoff * t60 / 1000.0f32.ln();
+            filter
+                .bandpass(cutoff.clamp(1.0, nyquist), q.max(0.5), env.sample_rate)
+                .map_err(ProcessorError::new)?;
+        }
+    }
+
+    let impulse = if *trig && !*last_trig { 1.0 } else { 0.0 };
+    *last_trig = *trig;
+
+    *out = 0.0;
+    for (mode, filter) in modes.iter().zip(filters.iter_mut()) {
+        if frequency * mode.ratio >= 0.9 * nyquist {
+            continue;
+        }
+        // scale the impulse so each mode rings at roughly its own gain
+        let excitation = input + impulse / (2.0 * filter.b0).max(f32::EPSILON);
+        *out += mode.gain * filter.process(excitation);
+    }
+
+    Ok(())
+}
+
+fn modal_resonator_allocate(proc: &mut ModalResonator, _sample_rate: f32, _block_size: usize) {
+    proc.filters = vec![BiquadState::default(); proc.modes.len()];
+    proc.last_params = [f32::NAN; 2];
+}
+
+impl ModalResonator {
+    pub fn new(modes: Vec<Mode>) -> Self {
+        let filters = vec![BiquadState::default(); modes.len()];
+        Self {
+            modes,
+            filters,
+            last_params: [f32::NAN; 2],
+            last_trig: false,
+            input: 0.0,
+            trig: false,
+            frequency: 220.0,
+            decay: 1.0,
+        }
+    }
+
+    pub fn preset(preset: ModalPreset) -> Self {
+        Self::new(preset.modes())
+    }
+}
+
+impl Default for ModalResonator {
+    fn default() -> Self {
+        Self::preset(ModalPreset::Bar)
+    }
+}
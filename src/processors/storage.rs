@@ -1,7 +1,7 @@
-use std::f32::consts::PI;
-
 use raug::prelude::*;
 
+use super::windowed_sinc;
+
 #[derive(Clone, Default)]
 pub struct SampleStorage {
     buf: Vec<f32>,
@@ -36,6 +36,34 @@ impl SampleStorage {
         Ok(Self { buf, sample_rate })
     }
 
+    /// Loads every channel of a file into its own [`SampleStorage`].
+    pub fn load_channels(path: &str) -> Result<Vec<Self>, hound::Error> {
+        let reader = hound::WavReader::open(path)?;
+        let spec = reader.spec();
+
+        let channels = spec.channels as usize;
+        let sample_rate = spec.sample_rate as f32;
+        let interleaved: Vec<f32> = match spec.sample_format {
+            hound::SampleFormat::Int => reader
+                .into_samples::<i32>()
+                .map(|s| s.map(|s| s as f32 / (1 << spec.bits_per_sample) as f32))
+                .collect::<Result<_, _>>()?,
+            hound::SampleFormat::Float => reader.into_samples::<f32>().collect::<Result<_, _>>()?,
+        };
+
+        Ok((0..channels)
+            .map(|channel| Self {
+                buf: interleaved
+                    .iter()
+                    .skip(channel)
+                    .step_by(channels)
+                    .copied()
+                    .collect(),
+                sample_rate,
+            })
+            .collect())
+    }
+
     #[inline]
     pub fn len(&self) -> usize {
         self.buf.len()
@@ -46,6 +74,11 @@ impl SampleStorage {
         self.buf.is_empty()
     }
 
+    #[inline]
+    pub fn as_slice(&self) -> &[f32] {
+        &self.buf
+    }
+
     pub fn resample(&mut self, new_sample_rate: f32) {
         if new_sample_rate != self.sample_rate && new_sample_rate > 0.0 {
             self.buf = resample(&self.buf, self.sample_rate, new_sample_rate);
@@ -94,26 +127,6 @@ pub fn sample(
     Ok(())
 }
 
-fn sinc(x: f32) -> f32 {
-    if x == 0.0 {
-        1.0
-    } else {
-        (PI * x).sin() / (PI * x)
-    }
-}
-
-fn hann(x: f32, half_width: f32) -> f32 {
-    0.5 * (1.0 + (PI * x / half_width).cos())
-}
-
-fn windowed_sinc(x: f32, half_width: f32) -> f32 {
-    if x.abs() < half_width {
-        sinc(x) * hann(x, half_width)
-    } else {
-        0.0
-    }
-}
-
 /// Resamples the input buffer using a high-quality sinc interpolation.
 fn resample(input: &[f32], input_rate: f32, output_rate: f32) -> Vec<f32> {
     if input_rate == 0.0 || output_rate == 0.0 {
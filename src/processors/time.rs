@@ -93,7 +93,7 @@ impl Decay {
 }
 
 #[derive(Clone, Copy, Default, PartialEq, Debug)]
-enum AdsrState {
+pub(crate) enum AdsrState {
     Attack,
     Decay,
     #[default]
@@ -107,6 +107,7 @@ pub fn adsr(
     #[state] state: &mut AdsrState,
     #[state] last_gate: &mut bool,
     #[state] value: &mut f32,
+    #[state] release_level: &mut f32,
 
     #[input] gate: &bool,
     #[input] attack: &f32,
@@ -125,6 +126,7 @@ pub fn adsr(
         *state = AdsrState::Attack;
     } else if !*gate && *last_gate {
         *state = AdsrState::Release;
+        *release_level = *value;
     }
 
     let slope = match *state {
@@ -144,8 +146,9 @@ pub fn adsr(
         }
         AdsrState::Sustain => 0.0,
         AdsrState::Release => {
+            // fall from wherever the gate was released, not from the sustain level
             if release > 0.0 {
-                -(1.0 - *sustain) / release
+                -*release_level / release
             } else {
                 -1.0
             }
@@ -177,6 +180,7 @@ impl Default for Adsr {
             state: AdsrState::Sustain,
             last_gate: false,
             value: 0.0,
+            release_level: 0.0,
             gate: false,
             attack: 0.0,
             decay: 0.0,
@@ -334,11 +338,77 @@ const fn catmull_rom(p0: f32, p1: f32, p2: f32, p3: f32, t: f32) -> f32 {
     a * t * t * t + b * t * t + c * t + d
 }
 
+/// A circular buffer that can be read at fractional delays, with Catmull-Rom interpolation.
+#[derive(Clone, Default)]
+pub struct DelayLine {
+    buf: Vec<f32>,
+    write_index: usize,
+}
+
+impl DelayLine {
+    pub fn new(len: usize) -> Self {
+        Self {
+            buf: vec![0.0; len],
+            write_index: 0,
+        }
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.buf.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.buf.is_empty()
+    }
+
+    /// Reads the signal as it was `delay` samples ago, where a delay of 1 is the most recent write.
+    #[inline]
+    pub fn read(&self, delay: f32) -> f32 {
+        let len = self.buf.len();
+        let read_index = (self.write_index as f32 - delay).rem_euclid(len as f32);
+
+        let index1 = read_index.floor() as usize % len;
+        let frac = read_index.fract();
+
+        let index0 = (index1 + len - 1) % len;
+        let index2 = (index1 + 1) % len;
+        let index3 = (index1 + 2) % len;
+
+        catmull_rom(
+            self.buf[index0],
+            self.buf[index1],
+            self.buf[index2],
+            self.buf[index3],
+            frac,
+        )
+    }
+
+    #[inline]
+    pub fn write(&mut self, value: f32) {
+        self.buf[self.write_index] = value;
+        self.write_index = (self.write_index + 1) % self.buf.len();
+    }
+
+    /// Replaces the sample written `delay` samples ago, where a delay of 1 is the most recent write.
+    #[inline]
+    pub fn overwrite(&mut self, delay: usize, value: f32) {
+        let len = self.buf.len();
+        self.buf[(self.write_index + len - delay % len) % len] = value;
+    }
+
+    /// Grows or shrinks the line to `len` samples, keeping the samples that still fit.
+    pub fn resize(&mut self, len: usize) {
+        self.buf.resize(len, 0.0);
+        self.write_index %= len.max(1);
+    }
+}
+
 #[processor]
 pub fn delay(
     env: ProcEnv,
-    #[state] ringbuf: &mut Vec<f32>,
-    #[state] write_index: &mut usize,
+    #[state] line: &mut DelayLine,
     #[input] input: &f32,
     #[input] delay: &f32,
     #[input] feedback: &f32,
@@ -346,33 +416,14 @@ pub fn delay(
 ) -> ProcResult<()> {
     let delay = delay.max(0.0);
     let delay_samples = delay * env.sample_rate;
-    if delay_samples >= ringbuf.len() as f32 {
-        ringbuf.resize((delay_samples + 3.0) as usize, 0.0); // we hate doing this here, but we have to
+    if delay_samples >= line.len() as f32 {
+        line.resize((delay_samples + 3.0) as usize); // we hate doing this here, but we have to
     }
-    let read_index = *write_index as f32 - delay_samples;
-    let read_index = if read_index < 0.0 {
-        ringbuf.len() as f32 + read_index
-    } else {
-        read_index
-    };
-
-    let index1 = read_index.floor() as usize % ringbuf.len();
-    let frac = read_index.fract();
-
-    let index0 = (index1 + ringbuf.len() - 1) % ringbuf.len();
-    let index2 = (index1 + 1) % ringbuf.len();
-    let index3 = (index1 + 2) % ringbuf.len();
 
-    let s0 = ringbuf[index0];
-    let s1 = ringbuf[index1];
-    let s2 = ringbuf[index2];
-    let s3 = ringbuf[index3];
-
-    *out = catmull_rom(s0, s1, s2, s3, frac);
+    *out = line.read(delay_samples);
 
     let feedback = feedback.clamp(-1.0, 1.0);
-    ringbuf[*write_index] = *input + feedback * *out;
-    *write_index = (*write_index + 1) % ringbuf.len();
+    line.write(*input + feedback * *out);
 
     Ok(())
 }
@@ -380,8 +431,7 @@ pub fn delay(
 impl Default for Delay {
     fn default() -> Self {
         Self {
-            ringbuf: vec![0.0; 1],
-            write_index: 0,
+            line: DelayLine::new(1),
             input: 0.0,
             feedback: 0.0,
             delay: 0.0,
@@ -392,8 +442,7 @@ impl Default for Delay {
 impl Delay {
     pub fn new(delay: f32) -> Self {
         Self {
-            ringbuf: vec![0.0; (delay * 48000.0) as usize + 1],
-            write_index: 0,
+            line: DelayLine::new((delay * 48000.0) as usize + 1),
             input: 0.0,
             feedback: 0.0,
             delay,
@@ -464,16 +513,17 @@ impl ReberbVoice {
         let mut lfo_phase = 0.0;
         SineOscillator::process_sample(
             env,
-            &mut self.lfo.t,
+            &mut self.lfo.osc,
             &self.lfo.phase,
             &self.lfo.frequency,
             &false,
+            &false,
+            &0.0,
             &mut lfo_phase,
         )?;
         Delay::process_sample(
             env,
-            &mut self.delay.ringbuf,
-            &mut self.delay.write_index,
+            &mut self.delay.line,
             &input,
             &(self.delay.delay + lfo_phase),
             &self.delay.feedback,
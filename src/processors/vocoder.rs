@@ -0,0 +1,113 @@
+use raug::prelude::*;
+
+use super::{BiquadState, EnvelopeFollower};
+
+pub const MIN_VOCODER_BANDS: usize = 8;
+pub const MAX_VOCODER_BANDS: usize = 40;
+
+/// The lowest and highest band centers, in Hz.
+const VOCODER_RANGE: (f32, f32) = (100.0, 8000.0);
+
+#[derive(Default, Clone, Copy)]
+pub struct VocoderBand {
+    modulator: [BiquadState; 2],
+    carrier: [BiquadState; 2],
+    follower: EnvelopeFollower,
+}
+
+/// A channel vocoder.
+///
+/// Both inputs are split into `bands` (8 to 40) log-spaced bands, and the envelope of each modulator
+/// band is imposed on the matching carrier band. `formant_shift` moves the carrier bands by that many
+/// semitones, and `attack` and `release` (in seconds) set how closely the envelopes are followed.
+#[processor(allocate = vocoder_allocate)]
+pub fn vocoder(
+    env: ProcEnv,
+    #[state] bank: &mut Vec<VocoderBand>,
+    #[state] last_params: &mut [f32; 3],
+    #[input] modulator: &f32,
+    #[input] carrier: &f32,
+    #[input] bands: &f32,
+    #[input] formant_shift: &f32,
+    #[input] attack: &f32,
+    #[input] release: &f32,
+    #[output] out: &mut f32,
+) -> ProcResult<()> {
+    let count = (bands.round() as usize)
+        .clamp(MIN_VOCODER_BANDS, MAX_VOCODER_BANDS)
+        .min(bank.len());
+
+    if *last_params != [count as f32, *formant_shift, env.sample_rate] {
+        *last_params = [count as f32, *formant_shift, env.sample_rate];
+
+        let (low, high) = VOCODER_RANGE;
+        let ratio = (high / low).powf(1.0 / (count - 1) as f32);
+        // each band's edges sit at the geometric midpoints between its neighbors, so adjacent bands meet
+        let q = ratio.sqrt() / (ratio - 1.0);
+        let shift = (formant_shift / 12.0).exp2();
+        let max = 0.45 * env.sample_rate;
+
+        for (i, band) in bank.iter_mut().take(count).enumerate() {
+            let center = low * ratio.powi(i as i32);
+            for section in band.modulator.iter_mut() {
+                section
+                    .bandpass(center.min(max), q, env.sample_rate)
+                    .map_err(ProcessorError::new)?;
+            }
+            for section in band.carrier.iter_mut() {
+                section
+                    .bandpass((center * shift).clamp(20.0, max), q, env.sample_rate)
+                    .map_err(ProcessorError::new)?;
+            }
+        }
+    }
+
+    let attack = EnvelopeFollower::coefficient(*attack, env.sample_rate);
+    let release = EnvelopeFollower::coefficient(*release, env.sample_rate);
+
+    *out = 0.0;
+    for band in bank.iter_mut().take(count) {
+        let m = band
+            .modulator
+            .iter_mut()
+            .fold(*modulator, |x, section| section.process(x));
+        let c = band
+            .carrier
+            .iter_mut()
+            .fold(*carrier, |x, section| section.process(x));
+        *out += c * band.follower.process(m, attack, release);
+    }
+    // narrower bands pass less of each signal, so make up for it as the band count grows
+    *out *= count as f32;
+
+    Ok(())
+}
+
+fn vocoder_allocate(proc: &mut Vocoder, _sample_rate: f32, _block_size: usize) {
+    proc.bank = vec![VocoderBand::default(); MAX_VOCODER_BANDS];
+    proc.last_params = [f32::NAN; 3];
+}
+
+impl Vocoder {
+    pub fn new(bands: usize) -> Self {
+        Self {
+            bands: bands as f32,
+            ..Default::default()
+        }
+    }
+}
+
+impl Default for Vocoder {
+    fn default() -> Self {
+        Self {
+            bank: vec![VocoderBand::default(); MAX_VOCODER_BANDS],
+            last_params: [f32::NAN; 3],
+            modulator: 0.0,
+            carrier: 0.0,
+            bands: 16.0,
+            formant_shift: 0.0,
+            attack: 0.005,
+            release: 0.05,
+        }
+    }
+}
@@ -0,0 +1,115 @@
+use std::f32::consts::PI;
+
+use raug::prelude::*;
+
+use super::BiquadState;
+
+/// Runs a signal at a multiple of the graph's sample rate, low-passing it before decimating back down.
+#[derive(Default, Clone, Copy)]
+pub struct Oversampler {
+    factor: usize,
+    filters: [BiquadState; 2],
+}
+
+impl Oversampler {
+    pub fn new(factor: usize) -> Self {
+        Self {
+            factor: factor.max(1),
+            ..Default::default()
+        }
+    }
+
+    #[inline]
+    pub fn factor(&self) -> usize {
+        self.factor.max(1)
+    }
+
+    /// Designs the decimation filter for the given base sample rate.
+    /// Call this from the owning processor's `allocate` hook.
+    pub fn allocate(&mut self, sample_rate: f32) {
+        let rate = sample_rate * self.factor() as f32;
+        // fourth-order Butterworth, just below the base rate's Nyquist
+        for (filter, q) in self.filters.iter_mut().zip([0.541_196, 1.306_563]) {
+            *filter = BiquadState::default();
+            // a fixed lowpass design always has a nonzero a0
+            let _ = filter.lowpass(0.45 * sample_rate, q, rate);
+        }
+    }
+
+    /// Calls `sample` once per sub-sample (with the sub-sample index) and returns the decimated result.
+    #[inline]
+    pub fn process(&mut self, mut sample: impl FnMut(usize) -> f32) -> f32 {
+        if self.factor() == 1 {
+            return sample(0);
+        }
+
+        let mut out = 0.0;
+        for k in 0..self.factor {
+            out = self
+                .filters
+                .iter_mut()
+                .fold(sample(k), |x, filter| filter.process(x));
+        }
+        out
+    }
+}
+
+/// A multi-stage sine wavefolder.
+///
+/// `fold` adds drive ahead of every stage, `symmetry` biases the input to fold its two halves
+/// differently, and `stages` (1 to 8) sets how many folding stages are chained.
+#[processor(allocate = wavefolder_allocate)]
+pub fn wavefolder(
+    #[state] oversampler: &mut Oversampler,
+    #[state] prev_input: &mut f32,
+    #[input] input: &f32,
+    #[input] fold: &f32,
+    #[input] symmetry: &f32,
+    #[input] stages: &f32,
+    #[output] out: &mut f32,
+) -> ProcResult<()> {
+    let drive = 1.0 + fold.max(0.0);
+    let stages = (stages.round() as usize).clamp(1, 8);
+    let factor = oversampler.factor() as f32;
+    let prev = *prev_input;
+
+    *out = oversampler.process(|k| {
+        // linearly interpolate the input up to the oversampled rate
+        let t = (k + 1) as f32 / factor;
+        let mut x = prev + (input - prev) * t + symmetry;
+        for _ in 0..stages {
+            x = (0.5 * PI * drive * x).sin();
+        }
+        x
+    });
+
+    *prev_input = *input;
+
+    Ok(())
+}
+
+fn wavefolder_allocate(proc: &mut Wavefolder, sample_rate: f32, _block_size: usize) {
+    proc.oversampler.allocate(sample_rate);
+}
+
+impl Wavefolder {
+    pub fn new(oversample: usize) -> Self {
+        Self {
+            oversampler: Oversampler::new(oversample),
+            ..Default::default()
+        }
+    }
+}
+
+impl Default for Wavefolder {
+    fn default() -> Self {
+        Self {
+            oversampler: Oversampler::new(4),
+            prev_input: 0.0,
+            input: 0.0,
+            fold: 1.0,
+            symmetry: 0.0,
+            stages: 1.0,
+        }
+    }
+}
@@ -0,0 +1,202 @@
+use raug::prelude::*;
+use rustfft::{FftPlanner, num_complex::Complex};
+use thiserror::Error;
+
+use super::{OscillatorCore, SampleStorage};
+
+#[derive(Error, Debug)]
+pub enum WavetableError {
+    #[error("Failed to load wavetable: {0}")]
+    Load(#[from] hound::Error),
+    #[error("Wavetable is empty")]
+    Empty,
+    #[error("Invalid frame size: {0}")]
+    InvalidFrameSize(usize),
+}
+
+/// A set of single-cycle frames, each stored as a chain of progressively band-limited mip levels.
+#[derive(Clone, Default)]
+pub struct Wavetable {
+    frame_size: usize,
+    frames: usize,
+    /// `levels[level][frame * frame_size + i]`, where level `k` contains harmonics up to `(frame_size / 2) >> k`.
+    levels: Vec<Vec<f32>>,
+}
+
+impl Wavetable {
+    /// Builds a wavetable from consecutive frames of `frame_size` samples each.
+    /// Trailing samples that don't fill a whole frame are ignored.
+    pub fn from_samples(samples: &[f32], frame_size: usize) -> Result<Self, WavetableError> {
+        if frame_size < 2 {
+            return Err(WavetableError::InvalidFrameSize(frame_size));
+        }
+
+        let frames = samples.len() / frame_size;
+        if frames == 0 {
+            return Err(WavetableError::Empty);
+        }
+
+        let mut planner = FftPlanner::<f32>::new();
+        let fft = planner.plan_fft_forward(frame_size);
+        let ifft = planner.plan_fft_inverse(frame_size);
+
+        let max_harmonic = frame_size / 2;
+        let num_levels = (usize::BITS - max_harmonic.leading_zeros()) as usize;
+        let mut levels = vec![vec![0.0; frames * frame_size]; num_levels];
+
+        let mut spectrum = vec![Complex::default(); frame_size];
+        let mut buf = vec![Complex::default(); frame_size];
+
+        for (frame, chunk) in samples.chunks_exact(frame_size).enumerate() {
+            for (bin, &x) in spectrum.iter_mut().zip(chunk) {
+                *bin = Complex::new(x, 0.0);
+            }
+            fft.process(&mut spectrum);
+
+            for (level, table) in levels.iter_mut().enumerate() {
+                let harmonics = max_harmonic >> level;
+
+                buf.fill(Complex::default());
+                for h in 1..=harmonics {
+                    buf[h] = spectrum[h];
+                    buf[frame_size - h] = spectrum[frame_size - h];
+                }
+                ifft.process(&mut buf);
+
+                let out = &mut table[frame * frame_size..(frame + 1) * frame_size];
+                for (y, x) in out.iter_mut().zip(&buf) {
+                    *y = x.re / frame_size as f32;
+                }
+            }
+        }
+
+        // normalize everything against the peak of the full-bandwidth level
+        let peak = levels[0].iter().fold(0.0f32, |peak, x| peak.max(x.abs()));
+        if peak > 0.0 {
+            for table in levels.iter_mut() {
+                for x in table.iter_mut() {
+                    *x /= peak;
+                }
+            }
+        }
+
+        Ok(Self {
+            frame_size,
+            frames,
+            levels,
+        })
+    }
+
+    #[inline]
+    pub fn frame_size(&self) -> usize {
+        self.frame_size
+    }
+
+    #[inline]
+    pub fn frames(&self) -> usize {
+        self.frames
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.frames == 0
+    }
+
+    /// Chooses the most detailed mip level whose harmonics all stay below Nyquist at the given frequency.
+    #[inline]
+    fn level_for(&self, frequency: f32, sample_rate: f32) -> usize {
+        let max_harmonic = 0.5 * sample_rate / frequency.abs().max(f32::EPSILON);
+        let mut level = 0;
+        while level + 1 < self.levels.len()
+            && ((self.frame_size / 2) >> level) as f32 > max_harmonic
+        {
+            level += 1;
+        }
+        level
+    }
+
+    #[inline]
+    fn read_frame(&self, table: &[f32], frame: usize, index: f32) -> f32 {
+        let frame = &table[frame * self.frame_size..(frame + 1) * self.frame_size];
+        let i0 = index as usize % self.frame_size;
+        let i1 = (i0 + 1) % self.frame_size;
+        let t = index.fract();
+        frame[i0] + (frame[i1] - frame[i0]) * t
+    }
+
+    /// Reads the table at `phase` (0..1), morphing between adjacent frames according to `position` (0..1).
+    #[inline]
+    pub fn read(&self, phase: f32, position: f32, frequency: f32, sample_rate: f32) -> f32 {
+        if self.is_empty() {
+            return 0.0;
+        }
+
+        let table = &self.levels[self.level_for(frequency, sample_rate)];
+        let index = phase * self.frame_size as f32;
+
+        let position = position.clamp(0.0, 1.0) * (self.frames - 1) as f32;
+        let frame0 = position as usize;
+        let frame1 = (frame0 + 1).min(self.frames - 1);
+        let t = position.fract();
+
+        let a = self.read_frame(table, frame0, index);
+        let b = self.read_frame(table, frame1, index);
+        a + (b - a) * t
+    }
+}
+
+#[processor]
+pub fn wavetable_oscillator(
+    env: ProcEnv,
+    #[state] table: &mut Wavetable,
+    #[state] osc: &mut OscillatorCore,
+    #[input] frequency: &f32,
+    #[input] position: &f32,
+    #[input] phase: &f32,
+    #[input] sync: &bool,
+    #[input] fm: &f32,
+    #[output] out: &mut f32,
+) -> ProcResult<()> {
+    *out = osc.next(*frequency, *phase, *fm, *sync, env.sample_rate, |p, _| {
+        table.read(p, *position, frequency + fm, env.sample_rate)
+    });
+
+    Ok(())
+}
+
+impl WavetableOscillator {
+    pub fn new(table: Wavetable) -> Self {
+        Self {
+            table,
+            ..Default::default()
+        }
+    }
+
+    /// Loads a single-cycle waveform, treating the whole file as one frame.
+    pub fn load(path: &str) -> Result<Self, WavetableError> {
+        let storage = SampleStorage::load(path)?;
+        let table = Wavetable::from_samples(storage.as_slice(), storage.len())?;
+        Ok(Self::new(table))
+    }
+
+    /// Loads a multi-frame wavetable made of consecutive frames of `frame_size` samples each.
+    pub fn load_frames(path: &str, frame_size: usize) -> Result<Self, WavetableError> {
+        let storage = SampleStorage::load(path)?;
+        let table = Wavetable::from_samples(storage.as_slice(), frame_size)?;
+        Ok(Self::new(table))
+    }
+}
+
+impl Default for WavetableOscillator {
+    fn default() -> Self {
+        Self {
+            table: Wavetable::default(),
+            osc: OscillatorCore::default(),
+            frequency: 440.0,
+            position: 0.0,
+            phase: 0.0,
+            sync: false,
+            fm: 0.0,
+        }
+    }
+}